@@ -0,0 +1,36 @@
+// Counts function declarations in a Lox file, nested ones included:
+// cargo run --example count_functions -- <filename>
+use std::{env, fs};
+
+use codecrafters_interpreter::visitor::{walk_expr, Visitor};
+use codecrafters_interpreter::{parser::Parser, scanner::Scanner, Expr};
+
+#[derive(Default)]
+struct FunctionCounter {
+    count: usize,
+}
+
+impl Visitor for FunctionCounter {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Function { .. } = expr {
+            self.count += 1;
+        }
+        walk_expr(self, expr);
+    }
+}
+
+fn main() {
+    let filename = env::args()
+        .nth(1)
+        .expect("Usage: count_functions <filename>");
+    let source = fs::read_to_string(&filename).expect("Failed to read file");
+
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens(&source, &mut 0);
+    let mut parser = Parser::new(scanner.tokens);
+    parser.parse();
+
+    let mut counter = FunctionCounter::default();
+    counter.visit_statements(&parser.statements);
+    println!("{}", counter.count);
+}
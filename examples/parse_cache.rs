@@ -0,0 +1,41 @@
+// Times repeated runs of one script with and without the parse cache:
+// `cargo run --release --example parse_cache`
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use codecrafters_interpreter::interpreter::Interpreter;
+
+const RUNS: usize = 500;
+
+fn time_runs(source: &str, cached: bool) -> Duration {
+    let mut interpreter = Interpreter::from_source(source);
+    interpreter.set_output(Rc::new(RefCell::new(io::sink())));
+    if cached {
+        interpreter.enable_parse_cache();
+    }
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        interpreter.run();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let source = (0..200)
+        .map(|i| format!("fun f{i}(a, b) {{ if (a > b) {{ return a - b; }} return b * {i}; }}\n"))
+        .collect::<String>()
+        + "print f199(1, 2);\n";
+
+    let uncached = time_runs(&source, false);
+    let cached = time_runs(&source, true);
+
+    println!("{} runs without cache: {:?}", RUNS, uncached);
+    println!("{} runs with cache:    {:?}", RUNS, cached);
+    println!(
+        "speedup: {:.1}x",
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
@@ -0,0 +1,36 @@
+// Times scanning identifier-heavy sources of growing size, each doubling should roughly
+// double the time: `cargo run --release --example scan_large`
+use std::time::{Duration, Instant};
+
+use codecrafters_interpreter::scanner::Scanner;
+
+fn time_scan(source: &str) -> Duration {
+    let start = Instant::now();
+    let mut scanner = Scanner::new();
+    let tokens = scanner.iter(source).filter_map(Result::ok).count();
+    let elapsed = start.elapsed();
+    assert!(tokens > 0);
+    elapsed
+}
+
+// Short and very long identifiers mixed with keywords and one long string per line
+fn source(bytes: usize) -> String {
+    let long_name = "identifier_".repeat(1_000);
+    let long_string = format!("\"{}\"", "text ".repeat(2_000));
+    let mut source = String::new();
+    let mut i = 0;
+    while source.len() < bytes {
+        source.push_str(&format!(
+            "var name{i} = {long_name}{i} and other_{i} or {long_string};\n"
+        ));
+        i += 1;
+    }
+    source
+}
+
+fn main() {
+    for megabytes in [1, 2, 4, 8] {
+        let source = source(megabytes * 1024 * 1024);
+        println!("{megabytes} MB: {:?}", time_scan(&source));
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use crate::Expr;
+
+// The parser settings a program was parsed with, the same source parsed with other settings
+// gives other statements
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    pub print_expressions: bool,
+    pub integers: bool,
+}
+
+struct Entry {
+    source: String,
+    options: ParseOptions,
+    statements: Rc<Vec<Expr>>,
+}
+
+// Parsed programs keyed by their source and parse options, running an unchanged script again
+// reuses its statements instead of scanning and parsing it a second time. Entries are found by
+// a hash but only used when the source matches too, so a collision can't run another program
+#[derive(Default)]
+pub struct ParseCache {
+    entries: HashMap<u64, Vec<Entry>>,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&mut self, source: &str, options: ParseOptions) -> Option<Rc<Vec<Expr>>> {
+        let statements = self
+            .entries
+            .get(&hash(source, options))
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .find(|entry| entry.source == source && entry.options == options)
+            })
+            .map(|entry| entry.statements.clone());
+        if statements.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        statements
+    }
+
+    pub fn insert(&mut self, source: &str, options: ParseOptions, statements: Rc<Vec<Expr>>) {
+        let entries = self.entries.entry(hash(source, options)).or_default();
+        match entries
+            .iter_mut()
+            .find(|entry| entry.source == source && entry.options == options)
+        {
+            Some(entry) => entry.statements = statements,
+            None => entries.push(Entry {
+                source: source.to_string(),
+                options,
+                statements,
+            }),
+        }
+    }
+}
+
+fn hash(source: &str, options: ParseOptions) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    options.hash(&mut hasher);
+    hasher.finish()
+}
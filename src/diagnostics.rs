@@ -0,0 +1,133 @@
+use std::fmt;
+
+use crate::error::{ParseError, ScanError};
+use crate::interpreter::Span;
+
+// How a lint finding is reported. Warnings are printed and the program still runs, errors stop
+// it before it starts like a parse error would. `--strict` makes every warning an error
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiagnosticLevel {
+    #[default]
+    Warning,
+    Error,
+}
+
+impl DiagnosticLevel {
+    fn severity(self) -> &'static str {
+        match self {
+            DiagnosticLevel::Warning => "warning",
+            DiagnosticLevel::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticLevel::Warning => f.write_str("Warning"),
+            DiagnosticLevel::Error => f.write_str("Error"),
+        }
+    }
+}
+
+// Suspicious but valid code: an assignment used as a condition, a local variable nobody reads or
+// statements after a `return`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: u32,
+    pub column: u32,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn at_level(&self, level: DiagnosticLevel) -> String {
+        format!("[line {}] {}: {}", self.line, level, self.message)
+    }
+
+    pub fn to_diagnostic(&self, level: DiagnosticLevel) -> Diagnostic {
+        Diagnostic {
+            severity: level.severity(),
+            line: self.line,
+            column: self.column,
+            message: self.message.clone(),
+            span: self.span,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.at_level(DiagnosticLevel::Warning))
+    }
+}
+
+// A scan or parse error or a lint finding in the shape editors read, printed by `--diagnostics-json`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: &'static str,
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+    pub span: Span,
+}
+
+impl From<&ScanError> for Diagnostic {
+    fn from(error: &ScanError) -> Self {
+        Self {
+            severity: "error",
+            line: error.line,
+            column: error.column,
+            message: error.message.clone(),
+            span: error.span,
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Self {
+            severity: "error",
+            line: error.line,
+            column: error.column,
+            message: error.message.clone(),
+            span: error.span,
+        }
+    }
+}
+
+impl Diagnostic {
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"severity\":\"{}\",\"line\":{},\"column\":{},\"message\":{},\"span\":{{\"start\":{},\"end\":{}}}}}",
+            self.severity,
+            self.line,
+            self.column,
+            json_string(&self.message),
+            self.span.start,
+            self.span.end
+        )
+    }
+}
+
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let items: Vec<String> = diagnostics.iter().map(Diagnostic::to_json).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -1,14 +1,16 @@
-use std::{cell::RefCell, collections::HashMap, process::exit, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{interpreter::Global, Expr};
+use crate::{error::RuntimeError, interpreter::Global, natives, Expr};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum EnvironmentValue {
     Expr(Expr),
     Global(Global),
+    // `var x;` under strict variables, reading it before an assignment is an error
+    Uninitialized,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Environment {
     pub map: RefCell<HashMap<String, EnvironmentValue>>,
     pub enclosing: Option<Rc<RefCell<Environment>>>,
@@ -22,19 +24,47 @@ impl Environment {
         }
     }
 
-    pub fn assign(&self, name: &str, value: EnvironmentValue) {
+    // Global scope with the natives already defined, see `Interpreter::run_with_env`
+    pub fn globals() -> Self {
+        let environment = Self::new();
+        natives::define_globals(&environment);
+        environment
+    }
+
+    // Adds a variable or native before a script runs, e.g.
+    // `Environment::globals().with_value("limit", EnvironmentValue::Expr(Expr::Number(10.0)))`
+    pub fn with_value(self, name: &str, value: EnvironmentValue) -> Self {
+        self.define(name, value);
+        self
+    }
+
+    // Changes the innermost variable with the name, unlike `define` it never creates one
+    pub fn assign(&self, name: &str, value: EnvironmentValue) -> Result<(), RuntimeError> {
         if self.check_definition(name) {
             self.map.borrow_mut().remove(name);
             self.map.borrow_mut().insert(name.to_string(), value);
-            return;
+            return Ok(());
         }
 
-        if let Some(ref enclosing) = self.enclosing {
-            enclosing.borrow().assign(name, value);
-            return;
+        match self.enclosing {
+            Some(ref enclosing) => enclosing.borrow().assign(name, value),
+            None => Err(RuntimeError::Error(format!(
+                "Undefined variable '{}'.",
+                name
+            ))),
         }
+    }
 
-        self.environment_error(&format!("Undefined variable '{}'", name));
+    // The scope at the end of the chain, where `global` declarations go
+    pub fn outermost(environment: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let mut current = Rc::clone(environment);
+        loop {
+            let enclosing = current.borrow().enclosing.clone();
+            match enclosing {
+                Some(enclosing) => current = enclosing,
+                None => return current,
+            }
+        }
     }
 
     pub fn set_enclosing(&mut self, enclosing: Rc<RefCell<Environment>>) {
@@ -54,23 +84,33 @@ impl Environment {
         self.map.borrow().contains_key(name)
     }
 
-    pub fn get(&self, name: &str, line: u32) -> Option<EnvironmentValue> {
-        if self.check_definition(name) {
-            if let Some(val) = self.map.borrow().get(name) {
-                return Some(val.clone());
-            }
+    // Walks the whole chain without failing, for inspecting environments from outside
+    pub fn try_get(&self, name: &str) -> Option<EnvironmentValue> {
+        if let Some(val) = self.map.borrow().get(name) {
+            return Some(val.clone());
         }
 
-        if let Some(enclosing) = &self.enclosing {
-            return enclosing.borrow().get(name, line);
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().try_get(name),
+            None => None,
         }
-        // println!("Undefined variable '{name}'");
-        // println!("[line {line}]");
-        self.environment_error(&format!("[line {}] Undefined variable '{}'", line, name))
     }
 
-    fn environment_error(&self, message: &str) -> Option<EnvironmentValue> {
-        // println!("{}", message);
-        exit(70);
+    // Variables defined directly in this scope, sorted by name so the order is stable
+    pub fn entries(&self) -> Vec<(String, EnvironmentValue)> {
+        let mut entries = self
+            .map
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    pub fn get(&self, name: &str, line: u32) -> Result<EnvironmentValue, RuntimeError> {
+        self.try_get(name).ok_or_else(|| {
+            RuntimeError::Error(format!("Undefined variable '{}'.\n[line {}]", name, line))
+        })
     }
 }
@@ -0,0 +1,89 @@
+use thiserror::Error;
+
+use crate::interpreter::Span;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum RuntimeError {
+    #[error("{0}")]
+    Error(String),
+    // Caused by one token of the source, `span` covers it so an editor can mark it
+    #[error("{message}")]
+    At {
+        message: String,
+        line: u32,
+        column: u32,
+        span: Span,
+    },
+    // Raised by the `exit` native, unwinds every call up to the interpreter which owns the
+    // exit code
+    #[error("Exit with code {0}.")]
+    Exit(i32),
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::At { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+// A declaration the parser could not make sense of, collected instead of stopping the parse
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("[line {line}] Error{location}: {message}")]
+pub struct ParseError {
+    pub line: u32,
+    // ` at 'lexeme'` for the token the parser stopped at, or ` at end`
+    pub location: String,
+    pub message: String,
+    pub column: u32,
+    pub span: Span,
+}
+
+// Why `Interpreter::run_capture` didn't finish the program
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum LoxError {
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+// Why `formula::evaluate` gave no value
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum FormulaError {
+    #[error(transparent)]
+    Scan(#[from] ScanError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+    // Assignments and calls, which could change state or run arbitrary code
+    #[error("{0}")]
+    Rejected(String),
+}
+
+// A character sequence the scanner could not turn into a token
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("[line {line}] Error: {message}")]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub line: u32,
+    pub message: String,
+    pub column: u32,
+    pub span: Span,
+}
+
+// What went wrong while scanning, so callers can tell failures apart without reading `message`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanErrorKind {
+    UnexpectedChar,
+    UnterminatedString,
+    UnterminatedComment,
+    // A backslash followed by something other than a known escape, or a bad `\u{...}`
+    InvalidEscape,
+    MalformedNumber,
+}
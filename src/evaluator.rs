@@ -1,20 +1,133 @@
 use std::cell::RefCell;
-use std::process::exit;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use crate::error::RuntimeError;
+use crate::formatters::NumberFormatter;
 use crate::interpreter::LoxCallable;
 use crate::{
     environment::{self, EnvironmentValue},
-    interpreter::{CallReturn, EvaluatorReturn, Global},
+    interpreter::{CallReturn, EvaluatorReturn, Pattern},
+    parser,
     runner::{self},
-    Expr, Literal, TokenType,
+    scanner, Expr, Literal, Token, TokenType,
 };
 
-pub struct Evaluator;
+// Output of `print` goes to a shared writer, so functions called during evaluation
+// print to the same place as the statements calling them
+#[derive(Clone)]
+pub struct Evaluator {
+    output: Rc<RefCell<dyn Write>>,
+    // Read by `read_all`, stdin unless the host gives another source
+    input: Rc<RefCell<dyn Read>>,
+    // File being evaluated, imports are resolved relative to its directory
+    file: Option<PathBuf>,
+    // Files whose import is still in progress, shared with evaluators of imported files
+    imports: Rc<RefCell<Vec<PathBuf>>>,
+    binding: BindingPolicy,
+    strict_variables: bool,
+    strict_booleans: bool,
+    print_expressions: bool,
+    integers: bool,
+    number_formatter: Option<NumberFormatter>,
+    sandbox: bool,
+}
+
+// When a function looks up the variables around it. Late binding sees their values at call
+// time, early binding sees them as they were when the function was declared
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BindingPolicy {
+    #[default]
+    Late,
+    Early,
+}
 
 impl Evaluator {
     pub fn new() -> Self {
-        Self {}
+        Self::with_output(Rc::new(RefCell::new(io::stdout())))
+    }
+
+    pub fn with_output(output: Rc<RefCell<dyn Write>>) -> Self {
+        Self {
+            output,
+            input: Rc::new(RefCell::new(io::stdin())),
+            file: None,
+            imports: Rc::new(RefCell::new(vec![])),
+            binding: BindingPolicy::default(),
+            strict_variables: false,
+            strict_booleans: false,
+            print_expressions: false,
+            integers: false,
+            sandbox: false,
+            number_formatter: None,
+        }
+    }
+
+    pub fn with_binding(mut self, binding: BindingPolicy) -> Self {
+        self.binding = binding;
+        self
+    }
+
+    // Variables declared without a value can't be read until something is assigned to them
+    pub fn with_strict_variables(mut self, strict_variables: bool) -> Self {
+        self.strict_variables = strict_variables;
+        self
+    }
+
+    // `and` and `or` give true or false instead of the operand that decided them
+    pub fn with_strict_booleans(mut self, strict_booleans: bool) -> Self {
+        self.strict_booleans = strict_booleans;
+        self
+    }
+
+    // `print x` is worth x, for programs parsed with `Parser::with_print_expressions`. Imported
+    // files are parsed that way too
+    pub fn with_print_expressions(mut self, print_expressions: bool) -> Self {
+        self.print_expressions = print_expressions;
+        self
+    }
+
+    // Imported files are parsed with `Parser::with_integers` as well
+    pub fn with_integers(mut self, integers: bool) -> Self {
+        self.integers = integers;
+        self
+    }
+
+    // Numbers written by `print` go through this instead of `formatters::format_number`
+    pub fn with_number_formatter(mut self, number_formatter: Option<NumberFormatter>) -> Self {
+        self.number_formatter = number_formatter;
+        self
+    }
+
+    // For scripts that aren't trusted, natives that could hold up the host such as `sleep` do
+    // nothing
+    pub fn with_sandbox(mut self, sandbox: bool) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    pub(crate) fn sandboxed(&self) -> bool {
+        self.sandbox
+    }
+
+    pub fn with_input(mut self, input: Rc<RefCell<dyn Read>>) -> Self {
+        self.input = input;
+        self
+    }
+
+    // Everything left in the input, for the `read_all` native
+    pub(crate) fn read_input(&self) -> io::Result<String> {
+        let mut text = String::new();
+        self.input.borrow_mut().read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    pub fn with_file(mut self, file: &Path) -> Self {
+        self.imports.borrow_mut().push(canonical(file));
+        self.file = Some(file.to_path_buf());
+        self
     }
 
     pub fn evaluate(
@@ -22,13 +135,34 @@ impl Evaluator {
         statement: &Expr,
         environment: &Rc<RefCell<environment::Environment>>,
         fn_bind: Option<&Expr>,
-    ) -> EvaluatorReturn {
-        self.evaluator(&statement, environment, fn_bind)
+    ) -> Result<EvaluatorReturn, RuntimeError> {
+        self.evaluator(statement, environment, fn_bind)
     }
 
-    fn invalid_error(&self, _message: String) -> Expr {
-        // println!("{}", _message);
-        exit(70)
+    fn invalid_error(&self, message: String) -> RuntimeError {
+        RuntimeError::Error(message)
+    }
+
+    // An error caused by `token`, it carries the token's position along with the message
+    fn error_at(&self, token: &Token, message: &str) -> RuntimeError {
+        RuntimeError::At {
+            message: format!("{}\n[line {}]", message, token.line),
+            line: token.line,
+            column: token.column,
+            span: token.span,
+        }
+    }
+
+    fn uninitialized_error(&self, name: &Token) -> RuntimeError {
+        self.error_at(
+            name,
+            &format!("Variable '{}' is used before being assigned.", name.lexeme),
+        )
+    }
+
+    // Comparing anything but two numbers or two strings, `nil < 1` included, is a runtime error
+    fn operands_error(&self, operator: &Token) -> RuntimeError {
+        self.error_at(operator, "Operands must be numbers.")
     }
 
     fn evaluator(
@@ -36,18 +170,21 @@ impl Evaluator {
         expr: &Expr,
         environment: &Rc<RefCell<environment::Environment>>,
         fn_bind: Option<&Expr>,
-    ) -> EvaluatorReturn {
+    ) -> Result<EvaluatorReturn, RuntimeError> {
         match expr {
-            Expr::Var(t) => {
-                let val = environment.borrow().get(&t.lexeme, t.line).unwrap().clone();
+            Expr::VarRef(t) => {
+                let val = environment.borrow().get(&t.lexeme, t.line)?;
                 // self.evaluator(&val, environment)
-                match val {
+                Ok(match val {
                     EnvironmentValue::Expr(e) => match &e {
                         Expr::Literal(_) => {
-                            EvaluatorReturn::Expr(self.expr_match(&e, environment, fn_bind))
+                            EvaluatorReturn::Expr(self.expr_match(&e, environment, fn_bind)?)
                         }
                         Expr::Function {
-                            name, params, body, environment
+                            name,
+                            params,
+                            body,
+                            environment,
                         } => EvaluatorReturn::Expr(Expr::Function {
                             name: name.clone(),
                             params: params.clone(),
@@ -57,18 +194,193 @@ impl Evaluator {
                         _ => EvaluatorReturn::Expr(e),
                     },
                     EnvironmentValue::Global(g) => EvaluatorReturn::Global(g.clone()),
+                    EnvironmentValue::Uninitialized => return Err(self.uninitialized_error(t)),
+                })
+            }
+            Expr::Get {
+                object,
+                name,
+                optional,
+            } => {
+                let object = self.evaluate_value(object, environment, fn_bind)?;
+                if *optional && matches!(object, Expr::Nil) {
+                    return Ok(EvaluatorReturn::Expr(Expr::Nil));
                 }
+                self.property(&object, name)
             }
-            _ => EvaluatorReturn::Expr(self.expr_match(expr, environment, fn_bind)),
+            Expr::Grouping(expr) => self.evaluate(expr, environment, fn_bind),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.evaluate_value(condition, environment, fn_bind)?;
+                let branch = if self.is_truthy(&condition) {
+                    then_branch
+                } else {
+                    else_branch
+                };
+                self.evaluate(branch, environment, fn_bind)
+            }
+            Expr::Set { .. } | Expr::IndexSet { .. } => Ok(EvaluatorReturn::Expr(
+                self.assign_member(expr, environment, fn_bind)?,
+            )),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => {
+                let object = self.evaluate_value(object, environment, fn_bind)?;
+                let index = self.evaluate_value(index, environment, fn_bind)?;
+                Ok(EvaluatorReturn::Expr(self.index(&object, &index, bracket)?))
+            }
+            _ => Ok(EvaluatorReturn::Expr(self.expr_match(
+                expr,
+                environment,
+                fn_bind,
+            )?)),
         }
     }
 
-    fn is_truthy(&self, expr: &Expr) -> bool {
-        match expr {
-            &Expr::Nil => false,
-            &Expr::Bool(b) => b,
-            _ => true,
+    // Evaluates to a plain value, literals become Expr::Bool, Expr::Number, Expr::String or
+    // Expr::Nil so every boolean is an Expr::Bool by the time it is compared or tested
+    fn evaluate_value(
+        &self,
+        expr: &Expr,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<Expr, RuntimeError> {
+        Ok(match self.evaluate(expr, environment, fn_bind)? {
+            EvaluatorReturn::Expr(Expr::Literal(l)) => literal_value(&l),
+            EvaluatorReturn::Expr(e) => e,
+            EvaluatorReturn::Global(native) => Expr::Native(native),
+        })
+    }
+
+    // Runs the top-level statements of another file in the global scope, or in a module
+    // scope bound to `alias`, a file importing itself through any chain of imports is an
+    // error instead of an endless loop
+    fn import(
+        &self,
+        path: &str,
+        alias: Option<&Token>,
+        line: u32,
+        environment: &Rc<RefCell<environment::Environment>>,
+    ) -> Result<(), RuntimeError> {
+        let file = match &self.file {
+            Some(current) => current.parent().unwrap_or(Path::new("")).join(path),
+            None => PathBuf::from(path),
+        };
+        let key = canonical(&file);
+
+        if self.imports.borrow().contains(&key) {
+            return Err(self.invalid_error(format!(
+                "Import cycle, '{}' is already being imported.\n[line {}]",
+                path, line
+            )));
+        }
+
+        let source = fs::read_to_string(&file).map_err(|_| {
+            self.invalid_error(format!("Could not read '{}'.\n[line {}]", path, line))
+        })?;
+
+        let mut error_code: u8 = 0;
+        let mut scanner = scanner::Scanner::new();
+        scanner.scan_tokens(&source, &mut error_code);
+        if error_code != 0 {
+            return Err(self.invalid_error(format!("{}: Could not scan file.", file.display())));
+        }
+        let mut parser = parser::Parser::new(scanner.tokens)
+            .with_print_expressions(self.print_expressions)
+            .with_integers(self.integers);
+        parser.parse();
+        if !parser.errors.is_empty() {
+            let errors = parser
+                .errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>();
+            return Err(self.invalid_error(format!("{}: {}", file.display(), errors.join("\n"))));
+        }
+
+        let evaluator = Self {
+            output: self.output.clone(),
+            input: self.input.clone(),
+            file: Some(file.clone()),
+            imports: self.imports.clone(),
+            binding: self.binding,
+            strict_variables: self.strict_variables,
+            strict_booleans: self.strict_booleans,
+            print_expressions: self.print_expressions,
+            integers: self.integers,
+            number_formatter: self.number_formatter.clone(),
+            sandbox: self.sandbox,
+        };
+        let mut globals = environment.clone();
+        while let Some(enclosing) = globals.clone().borrow().enclosing.clone() {
+            globals = enclosing;
+        }
+
+        // A module still sees globals such as natives, but its own declarations stay in it
+        let scope = match alias {
+            Some(_) => {
+                let mut module = environment::Environment::new();
+                module.set_enclosing(globals);
+                Rc::new(RefCell::new(module))
+            }
+            None => globals,
+        };
+
+        self.imports.borrow_mut().push(key);
+        let result = parser
+            .statements
+            .iter()
+            .try_for_each(|statement| evaluator.evaluate(statement, &scope, None).map(|_| ()));
+        self.imports.borrow_mut().pop();
+
+        // A span would point into the imported file, so only its message is passed on
+        result.map_err(|error| match error {
+            RuntimeError::Exit(code) => RuntimeError::Exit(code),
+            error => RuntimeError::Error(format!("{}: {}", file.display(), error)),
+        })?;
+
+        if let Some(alias) = alias {
+            environment.borrow().define(
+                &alias.lexeme,
+                EnvironmentValue::Expr(Expr::Module {
+                    name: alias.lexeme.clone(),
+                    environment: scope,
+                    read_only: false,
+                }),
+            );
+        }
+
+        Ok(())
+    }
+
+    // A `return` in the body goes on to the function, a `break` only ends the loop
+    fn do_while(
+        &self,
+        body: &Expr,
+        condition: &Expr,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<Expr, RuntimeError> {
+        loop {
+            match self.evaluate_value(body, environment, fn_bind)? {
+                returned @ Expr::Return(..) => return Ok(returned),
+                Expr::Break(_) => break,
+                _ => {}
+            }
+            if !self.is_truthy(&self.evaluate_value(condition, environment, fn_bind)?) {
+                break;
+            }
         }
+        Ok(Expr::Nil)
+    }
+
+    fn is_truthy(&self, expr: &Expr) -> bool {
+        expr.is_truthy()
     }
 
     fn expr_match(
@@ -76,197 +388,234 @@ impl Evaluator {
         expr: &Expr,
         environment: &Rc<RefCell<environment::Environment>>,
         fn_bind: Option<&Expr>,
-    ) -> Expr {
-        match expr {
-            Expr::Literal(l) => match l {
-                Literal::Bool(b) => Expr::Bool(*b),
-                Literal::String(s) => Expr::String(s.clone()),
-                Literal::Number(n) => Expr::Number(n.0),
-                _ => Expr::Nil,
-            },
+    ) -> Result<Expr, RuntimeError> {
+        Ok(match expr {
+            Expr::Literal(l) => literal_value(l),
             Expr::Print(e) => {
-                if let EvaluatorReturn::Expr(v) = self.evaluate(e, environment, fn_bind) {
-                    return Expr::Print(Box::new(v));
-                } else {
-                    return Expr::Nil;
-                }
+                let value = self.evaluate(e, environment, fn_bind)?;
+                let printed = match &value {
+                    EvaluatorReturn::Expr(e) if self.print_expressions => e.clone(),
+                    _ => Expr::Nil,
+                };
+                runner::interpret(
+                    &mut *self.output.borrow_mut(),
+                    value,
+                    self.number_formatter.as_ref(),
+                );
+                printed
             }
             Expr::Logical(left, right, operator) => {
-                let left = self.expr_match(left, environment, fn_bind.clone());
+                let left = self.evaluate_value(left, environment, fn_bind)?;
 
-                match operator {
+                let value = match operator {
                     TokenType::OR => {
                         if self.is_truthy(&left) {
                             left
                         } else {
-                            self.expr_match(right, environment, fn_bind)
+                            self.evaluate_value(right, environment, fn_bind)?
                         }
                     }
                     TokenType::AND => {
                         if !self.is_truthy(&left) {
                             left
                         } else {
-                            self.expr_match(right, environment, fn_bind)
+                            self.evaluate_value(right, environment, fn_bind)?
                         }
                     }
-                    _ => self.invalid_error(String::from("Logical error")),
+                    TokenType::QUESTION_QUESTION => {
+                        if matches!(left, Expr::Nil) {
+                            self.evaluate_value(right, environment, fn_bind)?
+                        } else {
+                            left
+                        }
+                    }
+                    _ => return Err(self.invalid_error(String::from("Logical error"))),
+                };
+                // `??` picks a value rather than a truth, so it is never turned into a boolean
+                if self.strict_booleans && *operator != TokenType::QUESTION_QUESTION {
+                    Expr::Bool(self.is_truthy(&value))
+                } else {
+                    value
                 }
             }
+            Expr::Match {
+                subject,
+                arms,
+                fallback,
+            } => {
+                let value = self.evaluate(subject, environment, fn_bind)?;
+                let arm = arms.iter().find(|(pattern, _)| match pattern {
+                    Pattern::Type(name) => type_name(&value) == name,
+                    Pattern::Value(literal) => match &value {
+                        EvaluatorReturn::Expr(e) => self.is_equal(e, &literal_value(literal)),
+                        EvaluatorReturn::Global(_) => false,
+                    },
+                });
+
+                let body = match arm {
+                    Some((_, body)) => body,
+                    None => match fallback {
+                        Some(fallback) => fallback,
+                        None => return Ok(Expr::Nil),
+                    },
+                };
+
+                // A `return` inside an arm has to reach the enclosing function
+                self.evaluate_value(body, environment, fn_bind)?
+            }
+            Expr::Import {
+                keyword,
+                path,
+                alias,
+            } => {
+                self.import(path, alias.as_ref(), keyword.line, environment)?;
+                Expr::Nil
+            }
             Expr::Assign { name, value } => {
-                let value_e = self.evaluate(value, environment, fn_bind);
-                if let EvaluatorReturn::Expr(e) = value_e {
-                    environment.borrow_mut().assign(name, EnvironmentValue::Expr(e.clone()));
-                    e
-                } else {
-                    self.invalid_error(String::from("Assign error"))
-                }
+                let e = stored(self.evaluate(value, environment, fn_bind)?);
+                // Reported with the line like reading an undefined variable is
+                environment
+                    .borrow_mut()
+                    .assign(&name.lexeme, EnvironmentValue::Expr(e.clone()))
+                    .map_err(|_| {
+                        self.error_at(name, &format!("Undefined variable '{}'.", name.lexeme))
+                    })?;
+                e
             }
             Expr::Block(vec) => {
                 let environment_clone = Rc::new(RefCell::new(environment::Environment::new()));
-                let mut evaluated: Expr;
                 let mut return_expr = Expr::Nil;
 
                 environment_clone.borrow_mut().enclosing = Some(environment.clone());
                 for expr in vec {
-                    match self.evaluate(expr, &environment_clone, fn_bind.clone()) {
-                        EvaluatorReturn::Expr(e) => match &e {
-                            Expr::Return(keyword, v) => {
-                                if let Some(_) = fn_bind {
-                                    return_expr = Expr::Return(keyword.clone(), v.clone());
-                                    break;
-                                } else {
-                                    self.invalid_error(String::from("Return error"));
-                                    break;
-                                }
+                    match self.evaluate(expr, &environment_clone, fn_bind)? {
+                        EvaluatorReturn::Expr(Expr::Return(keyword, v)) => {
+                            if fn_bind.is_some() {
+                                return_expr = Expr::Return(keyword, v);
+                                break;
+                            } else {
+                                return Err(self.invalid_error(String::from("Return error")));
                             }
-                            _ => evaluated = e,
-                        },
-                        _ => evaluated = Expr::Nil,
-                    };
-                    runner::interpret(evaluated)
+                        }
+                        // Left for the loop around the block to stop on
+                        EvaluatorReturn::Expr(Expr::Break(keyword)) => {
+                            return_expr = Expr::Break(keyword);
+                            break;
+                        }
+                        _ => {}
+                    }
                 }
 
                 return_expr
             }
-            Expr::Increment(i) => match self.evaluate(i, environment, fn_bind) {
-                EvaluatorReturn::Expr(e) => e,
-                _ => self.invalid_error(String::from("Increment error")),
-            },
             Expr::While(condition, body) => {
                 let mut evaluated: Expr;
 
-                let eval_condition = self.evaluate(condition, environment, fn_bind.clone());
-                if let EvaluatorReturn::Expr(mut e) = eval_condition {
-                    while self.is_truthy(&e) {
-                        evaluated = if let EvaluatorReturn::Expr(e) = self.evaluate(body, environment, fn_bind.clone()) {
-                            e
-                        } else {
-                            Expr::Nil
-                        };
-                        match &evaluated {
-                            Expr::Return(..) => return evaluated,
-                            _ => {}
-                        }
-                        runner::interpret(evaluated);
-
-                        e = if let EvaluatorReturn::Expr(e) =
-                            self.evaluate(condition, environment, fn_bind.clone())
-                        {
-                            e
-                        } else {
-                            Expr::Nil
-                        }
+                let mut e = stored(self.evaluate(condition, environment, fn_bind)?);
+                while self.is_truthy(&e) {
+                    evaluated = stored(self.evaluate(body, environment, fn_bind)?);
+                    match &evaluated {
+                        Expr::Return(..) => return Ok(evaluated),
+                        Expr::Break(_) => break,
+                        _ => {}
                     }
+
+                    e = stored(self.evaluate(condition, environment, fn_bind)?);
                 }
 
                 Expr::Nil
             }
+            Expr::DoWhile(body, condition) => {
+                self.do_while(body, condition, environment, fn_bind)?
+            }
+            Expr::Iteration { names, body } => self.iteration(names, body, environment, fn_bind)?,
+            Expr::Break(_) => expr.clone(),
             Expr::Function {
                 name, params, body, ..
             } => {
-                environment.borrow_mut().define(
-                    &name.lexeme,
-                    EnvironmentValue::Expr(Expr::Function {
-                        name: name.clone(),
-                        params: params.clone(),
-                        body: body.clone(),
-                        environment: Some(environment.clone()),
-                    }),
-                );
+                // With early binding the function closes over a copy of its defining scope,
+                // the function itself is added to the copy so it can still call itself
+                let closure = match self.binding {
+                    BindingPolicy::Late => environment.clone(),
+                    BindingPolicy::Early => Rc::new(RefCell::new(environment.borrow().clone())),
+                };
+                let function = EnvironmentValue::Expr(Expr::Function {
+                    name: name.clone(),
+                    params: params.clone(),
+                    body: body.clone(),
+                    environment: Some(closure.clone()),
+                });
+                if self.binding == BindingPolicy::Early {
+                    closure.borrow().define(&name.lexeme, function.clone());
+                }
+                environment.borrow_mut().define(&name.lexeme, function);
                 Expr::String(format!("<fn {}>", name.lexeme))
             }
-            Expr::Call(callee, _, args) => {
-                let callee_ev = self.evaluate(callee, environment, fn_bind.clone());
+            Expr::Call(callee, paren, args) => {
+                let callee_ev = match self.evaluate(callee, environment, fn_bind)? {
+                    EvaluatorReturn::Expr(Expr::Native(native)) => EvaluatorReturn::Global(native),
+                    callee_ev => callee_ev,
+                };
 
                 let mut arguments = vec![];
 
                 for argument in args {
-                    if let EvaluatorReturn::Expr(e) =
-                        self.evaluate(argument, environment, fn_bind.clone())
-                    {
-                        arguments.push(e);
-                    }
+                    arguments.push(match self.evaluate(argument, environment, fn_bind)? {
+                        EvaluatorReturn::Expr(e) => e,
+                        EvaluatorReturn::Global(native) => Expr::Native(native),
+                    });
                 }
 
                 match &callee_ev {
                     EvaluatorReturn::Expr(e) => match e {
                         Expr::Function { .. } => {
-                            if !e.is_lox_callable(&callee) {
-                                self.invalid_error(String::from(
-                                    "Can only call functions and classes.",
-                                ));
-                                return Expr::Nil;
-                            }
-
                             if arguments.len() != e.arity() {
-                                self.invalid_error(format!(
-                                    "Expected {} arguments but got {}.",
-                                    e.arity(),
-                                    arguments.len()
+                                return Err(self.error_at(
+                                    paren,
+                                    &format!(
+                                        "Expected {} arguments but got {}.",
+                                        e.arity(),
+                                        arguments.len()
+                                    ),
                                 ));
-                                return Expr::Nil;
                             }
 
-                            match e.call(environment, fn_bind, arguments) {
+                            match e.call(self, environment, fn_bind, arguments)? {
                                 CallReturn::Expr(e) => e,
                             }
                         }
-                        _ => exit(70),
+                        _ => {
+                            return Err(self.error_at(paren, "Can only call functions and classes."))
+                        }
                     },
-                    EvaluatorReturn::Global(g) => match g {
-                        Global::Clock(c) => {
-                            if !c.is_lox_callable(&callee) {
-                                self.invalid_error(String::from(
-                                    "Can only call functions and classes.",
-                                ));
-                            }
-
-                            if arguments.len() != c.arity() {
-                                self.invalid_error(format!(
+                    EvaluatorReturn::Global(g) => {
+                        if arguments.len() < g.arity() || arguments.len() > g.max_arity() {
+                            let expected = if g.max_arity() == g.arity() {
+                                g.arity().to_string()
+                            } else {
+                                format!("{} to {}", g.arity(), g.max_arity())
+                            };
+                            return Err(self.error_at(
+                                paren,
+                                &format!(
                                     "Expected {} arguments but got {}.",
-                                    c.arity(),
+                                    expected,
                                     arguments.len()
-                                ));
-                            }
+                                ),
+                            ));
+                        }
 
-                            match c.call(environment, fn_bind, arguments) {
-                                CallReturn::Expr(e) => e,
-                            }
+                        match g.call(self, environment, fn_bind, arguments)? {
+                            CallReturn::Expr(e) => e,
                         }
-                    },
+                    }
                 }
             }
             Expr::Return(keyword, value) => {
                 let mut value_ev = Expr::Nil;
 
                 if **value != Expr::Nil {
-                    value_ev = if let EvaluatorReturn::Expr(e) =
-                        self.evaluate(value, environment, fn_bind)
-                    {
-                        e
-                    } else {
-                        Expr::Nil
-                    };
+                    value_ev = stored(self.evaluate(value, environment, fn_bind)?);
                 }
 
                 Expr::Return(keyword.clone(), Box::new(value_ev))
@@ -276,168 +625,586 @@ impl Evaluator {
                 then_branch,
                 else_branch,
             } => {
-                if let EvaluatorReturn::Expr(e) =
-                    self.evaluate(condition, environment, fn_bind.clone())
-                {
-                    if self.is_truthy(&e) {
-                        if let EvaluatorReturn::Expr(e) =
-                            self.evaluate(then_branch, environment, fn_bind)
-                        {
-                            return e;
-                        } else {
-                            return Expr::Nil;
-                        }
-                    } else if let Some(else_branch) = else_branch {
-                        if let EvaluatorReturn::Expr(e) =
-                            self.evaluate(else_branch, environment, fn_bind)
-                        {
-                            return e;
-                        } else {
-                            return Expr::Nil;
-                        }
-                    } else {
-                        Expr::Nil
-                    }
+                let e = stored(self.evaluate(condition, environment, fn_bind)?);
+                if self.is_truthy(&e) {
+                    stored(self.evaluate(then_branch, environment, fn_bind)?)
+                } else if let Some(else_branch) = else_branch {
+                    stored(self.evaluate(else_branch, environment, fn_bind)?)
                 } else {
-                    self.invalid_error(String::from("If condition error"))
+                    Expr::Nil
                 }
             }
-            Expr::Variable { name, value } => {
-                let value_def = self.evaluate(value, environment, fn_bind);
-                if let EvaluatorReturn::Expr(e) = value_def {
-                    environment.borrow().define(name, EnvironmentValue::Expr(e.clone()));
-                    Expr::Variable {
-                        name: name.clone(),
-                        value: Box::new(e),
-                    }
-                } else {
-                    self.invalid_error(String::from("Variable error"))
+            Expr::VarDecl { name, value }
+                if self.strict_variables && matches!(**value, Expr::Nil) =>
+            {
+                environment
+                    .borrow()
+                    .define(name, EnvironmentValue::Uninitialized);
+                Expr::VarDecl {
+                    name: name.clone(),
+                    value: Box::new(Expr::Nil),
+                }
+            }
+            Expr::GlobalDecl { name, value } => {
+                self.global_declaration(name, value, environment, fn_bind)?;
+                Expr::Nil
+            }
+            Expr::Destructure { names, value } => {
+                self.destructure(names, value, environment, fn_bind)?;
+                Expr::Nil
+            }
+            Expr::VarDecl { name, value } => {
+                let e = stored(self.evaluate(value, environment, fn_bind)?);
+                environment
+                    .borrow()
+                    .define(name, EnvironmentValue::Expr(e.clone()));
+                Expr::VarDecl {
+                    name: name.clone(),
+                    value: Box::new(e),
                 }
             }
             Expr::Binary {
                 operator,
                 left,
                 right,
-            } => {
-                let left = self.evaluate(left, environment, fn_bind.clone());
-                let right = self.evaluate(right, environment, fn_bind);
-
-                match (left, right) {
-                    (EvaluatorReturn::Expr(left), EvaluatorReturn::Expr(right)) => {
-                        match operator.token_type {
-                            TokenType::MINUS => {
-                                match (left, right) {
-                                    // Here i convert the left and right values to Expr::Number and use
-                                    // them
-                                    (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 - n2),
-                                    _ => self.invalid_error(String::from("Binary minus error")),
-                                }
-                            }
-                            TokenType::SLASH => match (left, right) {
-                                (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 / n2),
-                                _ => self.invalid_error(String::from("Binary slash error")),
-                            },
-                            TokenType::STAR => match (left, right) {
-                                (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 * n2),
-                                _ => self.invalid_error(String::from("Binary star error")),
-                            },
-                            TokenType::PLUS => match (left, right) {
-                                (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 + n2),
-                                (Expr::String(s1), Expr::String(s2)) => {
-                                    Expr::String(format!("{}{}", s1, s2))
-                                }
-                                _ => self.invalid_error(String::from("Binary plus error")),
-                            },
-                            TokenType::GREATER => match (left, right) {
-                                (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 > n2),
-                                _ => self.invalid_error(String::from("Binary greater error")),
-                            },
-                            TokenType::GREATER_EQUAL => match (left, right) {
-                                (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 >= n2),
-                                _ => self.invalid_error(String::from("Binary greater equal error")),
-                            },
-                            TokenType::LESS => match (left, right) {
-                                (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 < n2),
-                                _ => self.invalid_error(String::from("Binary less error")),
-                            },
-                            TokenType::LESS_EQUAL => match (left, right) {
-                                (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 <= n2),
-                                _ => self.invalid_error(String::from("Binary less equal error")),
-                            },
-                            TokenType::EQUAL_EQUAL => {
-                                if self.is_equal(left, right) {
-                                    Expr::Bool(true)
-                                } else {
-                                    Expr::Bool(false)
-                                }
-                            }
-                            TokenType::BANG_EQUAL => {
-                                if self.is_equal(left, right) {
-                                    Expr::Bool(false)
-                                } else {
-                                    Expr::Bool(true)
-                                }
-                            }
-                            _ => Expr::Nil,
+            } => self.binary(operator, left, right, environment, fn_bind)?,
+            Expr::Unary { operator, right } => {
+                let evaluated = self.evaluate(right, environment, fn_bind)?;
+                match operator.token_type {
+                    // Natives are values like any other, so they count as truthy
+                    TokenType::BANG => match evaluated {
+                        EvaluatorReturn::Expr(e) => Expr::Bool(!self.is_truthy(&e)),
+                        EvaluatorReturn::Global(_) => Expr::Bool(false),
+                    },
+                    TokenType::MINUS => match evaluated {
+                        EvaluatorReturn::Expr(Expr::Number(n)) => Expr::Number(-n),
+                        EvaluatorReturn::Expr(Expr::Int(n)) => Expr::Int(
+                            n.checked_neg()
+                                .ok_or_else(|| self.error_at(operator, "Integer overflow."))?,
+                        ),
+                        _ => {
+                            return Err(self.error_at(operator, "Operand must be a number."));
                         }
-                    }
+                    },
                     _ => Expr::Nil,
                 }
             }
-            Expr::Unary { operator, right } => {
-                let evaluated = self.evaluate(right, environment, fn_bind.clone());
-                if let EvaluatorReturn::Expr(e) = evaluated {
-                    match operator.token_type {
-                        TokenType::BANG => match e {
-                            Expr::Bool(b) => Expr::Bool(!b),
-                            Expr::Unary {
-                                operator: _,
-                                right: _,
-                            } => {
-                                if let EvaluatorReturn::Expr(e_u) =
-                                    self.evaluate(right, environment, fn_bind)
-                                {
-                                    e_u
-                                } else {
-                                    Expr::Nil
-                                }
-                            }
-                            Expr::Nil => Expr::Bool(true),
-                            _ => Expr::Nil,
-                        },
-                        TokenType::MINUS => {
-                            match e {
-                                Expr::Number(n) => Expr::Number(-n),
-                                _ => {
-                                    /*
-                                    println!("Operand must be a number.");
-                                    println!("[line {}]", operator.line);
-                                    */
-                                    self.invalid_error(String::from("Unary minus error"))
-                                }
-                            }
+            Expr::List(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate_value(element, environment, fn_bind)?);
+                }
+                Expr::Array(Rc::new(RefCell::new(values)))
+            }
+            Expr::Array(_) | Expr::Int(_) => expr.clone(),
+            Expr::ObjectLiteral(fields) => self.object(fields, environment, fn_bind)?,
+            Expr::Object(_) => expr.clone(),
+            _ => Expr::Nil,
+        })
+    }
+
+    // Arrays hand out their elements and strings one character long strings
+    fn index(&self, object: &Expr, index: &Expr, bracket: &Token) -> Result<Expr, RuntimeError> {
+        let position = self.position(index, bracket)?;
+
+        let element = match object {
+            Expr::Array(elements) => elements.borrow().get(position).cloned(),
+            Expr::String(s) => s.chars().nth(position).map(|c| Expr::String(c.to_string())),
+            _ => return Err(self.error_at(bracket, "Only arrays and strings can be indexed.")),
+        };
+
+        element.ok_or_else(|| self.out_of_bounds(position, bracket))
+    }
+
+    // Kept out of evaluate so its stack frame, which every nested call pays for, stays small
+    fn assign_member(
+        &self,
+        expr: &Expr,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<Expr, RuntimeError> {
+        match expr {
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                let object = self.evaluate_value(object, environment, fn_bind)?;
+                let value = self.evaluate_value(value, environment, fn_bind)?;
+                self.set_property(&object, name, &value)?;
+                Ok(value)
+            }
+            Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+            } => {
+                let object = self.evaluate_value(object, environment, fn_bind)?;
+                let index = self.evaluate_value(index, environment, fn_bind)?;
+                let value = self.evaluate_value(value, environment, fn_bind)?;
+                self.set_index(&object, &index, bracket, &value)?;
+                Ok(value)
+            }
+            _ => unreachable!("only called for assignments to a property or element"),
+        }
+    }
+
+    fn iteration(
+        &self,
+        names: &[String],
+        body: &Expr,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<Expr, RuntimeError> {
+        let mut pass = environment::Environment::new();
+        pass.set_enclosing(environment.clone());
+        for name in names {
+            if let Some(value) = environment.borrow().try_get(name) {
+                pass.define(name, value);
+            }
+        }
+        let pass = Rc::new(RefCell::new(pass));
+
+        let evaluated = self.evaluate(body, &pass, fn_bind);
+        for (name, value) in pass.borrow().entries() {
+            if names.contains(&name) {
+                environment.borrow().assign(&name, value)?;
+            }
+        }
+
+        Ok(match evaluated? {
+            EvaluatorReturn::Expr(e) => e,
+            EvaluatorReturn::Global(native) => Expr::Native(native),
+        })
+    }
+
+    fn global_declaration(
+        &self,
+        name: &str,
+        value: &Expr,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<(), RuntimeError> {
+        let globals = environment::Environment::outermost(environment);
+        if matches!(value, Expr::Nil) && globals.borrow().check_definition(name) {
+            return Ok(());
+        }
+
+        let value = match self.evaluate(value, environment, fn_bind)? {
+            EvaluatorReturn::Expr(e) => EnvironmentValue::Expr(e),
+            EvaluatorReturn::Global(g) => EnvironmentValue::Global(g),
+        };
+        globals.borrow().define(name, value);
+        Ok(())
+    }
+
+    fn destructure(
+        &self,
+        names: &[Token],
+        value: &Expr,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<(), RuntimeError> {
+        let values = match self.evaluate_value(value, environment, fn_bind)? {
+            Expr::Array(elements) => elements.borrow().clone(),
+            _ => return Err(self.error_at(&names[0], "Only an array can be split into variables.")),
+        };
+        if values.len() != names.len() {
+            return Err(self.error_at(
+                &names[0],
+                &format!("Expected {} values but got {}.", names.len(), values.len()),
+            ));
+        }
+
+        for (name, value) in names.iter().zip(values) {
+            environment
+                .borrow()
+                .define(&name.lexeme, EnvironmentValue::Expr(value));
+        }
+        Ok(())
+    }
+
+    // Only members the module declared can be changed, it can't gain new ones
+    fn property(&self, object: &Expr, name: &Token) -> Result<EvaluatorReturn, RuntimeError> {
+        match object {
+            Expr::Module { environment, .. } => {
+                let member = environment.borrow().map.borrow().get(&name.lexeme).cloned();
+                match member {
+                    Some(EnvironmentValue::Expr(e)) => Ok(EvaluatorReturn::Expr(e)),
+                    Some(EnvironmentValue::Global(g)) => Ok(EvaluatorReturn::Global(g)),
+                    Some(EnvironmentValue::Uninitialized) => Err(self.uninitialized_error(name)),
+                    None => {
+                        Err(self.error_at(name, &format!("Undefined property '{}'.", name.lexeme)))
+                    }
+                }
+            }
+            Expr::Object(fields) => match field(&fields.borrow(), &name.lexeme) {
+                Some(value) => Ok(EvaluatorReturn::Expr(value.clone())),
+                None => Err(self.error_at(name, &format!("Undefined property '{}'.", name.lexeme))),
+            },
+            _ => Err(self.error_at(name, "Only modules and objects have properties.")),
+        }
+    }
+
+    fn object(
+        &self,
+        fields: &[(Token, Expr)],
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<Expr, RuntimeError> {
+        let mut values = Vec::with_capacity(fields.len());
+        for (name, value) in fields {
+            let value = self.evaluate_value(value, environment, fn_bind)?;
+            values.push((name.lexeme.clone(), value));
+        }
+        Ok(Expr::Object(Rc::new(RefCell::new(values))))
+    }
+
+    fn set_property(&self, object: &Expr, name: &Token, value: &Expr) -> Result<(), RuntimeError> {
+        match object {
+            Expr::Module {
+                name: module,
+                read_only: true,
+                ..
+            } => Err(self.error_at(name, &format!("Can't change members of '{}'.", module))),
+            Expr::Module { environment, .. } => {
+                let environment = environment.borrow();
+                let mut members = environment.map.borrow_mut();
+                match members.get_mut(&name.lexeme) {
+                    Some(member) => {
+                        *member = EnvironmentValue::Expr(value.clone());
+                        Ok(())
+                    }
+                    None => {
+                        Err(self.error_at(name, &format!("Undefined property '{}'.", name.lexeme)))
+                    }
+                }
+            }
+            // Setting a field an object doesn't have yet adds it
+            Expr::Object(fields) => {
+                let mut fields = fields.borrow_mut();
+                match fields.iter_mut().find(|(field, _)| *field == name.lexeme) {
+                    Some((_, field)) => *field = value.clone(),
+                    None => fields.push((name.lexeme.clone(), value.clone())),
+                }
+                Ok(())
+            }
+            _ => Err(self.error_at(name, "Only modules and objects have properties.")),
+        }
+    }
+
+    fn set_index(
+        &self,
+        object: &Expr,
+        index: &Expr,
+        bracket: &Token,
+        value: &Expr,
+    ) -> Result<(), RuntimeError> {
+        let position = self.position(index, bracket)?;
+        match object {
+            Expr::Array(elements) => match elements.borrow_mut().get_mut(position) {
+                Some(element) => {
+                    *element = value.clone();
+                    Ok(())
+                }
+                None => Err(self.out_of_bounds(position, bracket)),
+            },
+            _ => Err(self.error_at(bracket, "Only array elements can be assigned.")),
+        }
+    }
+
+    fn binary(
+        &self,
+        operator: &Token,
+        left: &Expr,
+        right: &Expr,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+    ) -> Result<Expr, RuntimeError> {
+        let left = self.evaluate(left, environment, fn_bind)?;
+        let right = self.evaluate(right, environment, fn_bind)?;
+
+        Ok(match (left, right) {
+            (EvaluatorReturn::Expr(left), EvaluatorReturn::Expr(right))
+                if matches!(
+                    (&left, &right),
+                    (Expr::Int(_), Expr::Int(_) | Expr::Number(_))
+                        | (Expr::Number(_), Expr::Int(_))
+                ) =>
+            {
+                self.integer_binary(operator, &left, &right)?
+            }
+            (EvaluatorReturn::Expr(left), EvaluatorReturn::Expr(right)) => {
+                match operator.token_type {
+                    TokenType::MINUS => {
+                        match (left, right) {
+                            // Here i convert the left and right values to Expr::Number and use
+                            // them
+                            (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 - n2),
+                            _ => return Err(self.operands_error(operator)),
                         }
-                        _ => Expr::Nil,
                     }
-                } else {
-                    Expr::Nil
+                    TokenType::SLASH => match (left, right) {
+                        (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 / n2),
+                        _ => return Err(self.operands_error(operator)),
+                    },
+                    TokenType::STAR => match (left, right) {
+                        (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 * n2),
+                        _ => return Err(self.operands_error(operator)),
+                    },
+                    TokenType::PLUS => match (left, right) {
+                        (Expr::Number(n1), Expr::Number(n2)) => Expr::Number(n1 + n2),
+                        (Expr::String(s1), Expr::String(s2)) => {
+                            Expr::String(format!("{}{}", s1, s2))
+                        }
+                        _ => {
+                            return Err(self.error_at(
+                                operator,
+                                "Operands must be two numbers or two strings.",
+                            ))
+                        }
+                    },
+                    TokenType::GREATER => match (left, right) {
+                        (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 > n2),
+                        (Expr::String(s1), Expr::String(s2)) => Expr::Bool(s1 > s2),
+                        _ => return Err(self.operands_error(operator)),
+                    },
+                    TokenType::GREATER_EQUAL => match (left, right) {
+                        (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 >= n2),
+                        (Expr::String(s1), Expr::String(s2)) => Expr::Bool(s1 >= s2),
+                        _ => return Err(self.operands_error(operator)),
+                    },
+                    TokenType::LESS => match (left, right) {
+                        (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 < n2),
+                        (Expr::String(s1), Expr::String(s2)) => Expr::Bool(s1 < s2),
+                        _ => return Err(self.operands_error(operator)),
+                    },
+                    TokenType::LESS_EQUAL => match (left, right) {
+                        (Expr::Number(n1), Expr::Number(n2)) => Expr::Bool(n1 <= n2),
+                        (Expr::String(s1), Expr::String(s2)) => Expr::Bool(s1 <= s2),
+                        _ => return Err(self.operands_error(operator)),
+                    },
+                    TokenType::EQUAL_EQUAL => Expr::Bool(self.is_equal(&left, &right)),
+                    TokenType::BANG_EQUAL => Expr::Bool(!self.is_equal(&left, &right)),
+                    _ => Expr::Nil,
                 }
             }
-            Expr::Grouping(exprs) => {
-                if let EvaluatorReturn::Expr(e_u) = self.evaluate(&exprs[0], environment, fn_bind) {
-                    e_u
-                } else {
-                    Expr::Nil
+            // A native on either side, which only equals the same native
+            (left, right) => {
+                let same = matches!(
+                    (&left, &right),
+                    (EvaluatorReturn::Global(a), EvaluatorReturn::Global(b)) if a == b
+                );
+                match operator.token_type {
+                    TokenType::EQUAL_EQUAL => Expr::Bool(same),
+                    TokenType::BANG_EQUAL => Expr::Bool(!same),
+                    TokenType::PLUS => {
+                        return Err(
+                            self.error_at(operator, "Operands must be two numbers or two strings.")
+                        )
+                    }
+                    _ => return Err(self.operands_error(operator)),
                 }
             }
+        })
+    }
+
+    // Two integers give an integer, `/` included, which rounds toward zero. An integer next to a
+    // float is turned into a float first, so the result is a float as without `--integers`
+    fn integer_binary(
+        &self,
+        operator: &Token,
+        left: &Expr,
+        right: &Expr,
+    ) -> Result<Expr, RuntimeError> {
+        let (a, b) = match (left, right) {
+            (Expr::Int(a), Expr::Int(b)) => (*a, *b),
+            _ => {
+                let (a, b) = (left.as_number().unwrap(), right.as_number().unwrap());
+                return Ok(match operator.token_type {
+                    TokenType::PLUS => Expr::Number(a + b),
+                    TokenType::MINUS => Expr::Number(a - b),
+                    TokenType::STAR => Expr::Number(a * b),
+                    TokenType::SLASH => Expr::Number(a / b),
+                    TokenType::GREATER => Expr::Bool(a > b),
+                    TokenType::GREATER_EQUAL => Expr::Bool(a >= b),
+                    TokenType::LESS => Expr::Bool(a < b),
+                    TokenType::LESS_EQUAL => Expr::Bool(a <= b),
+                    TokenType::EQUAL_EQUAL => Expr::Bool(a == b),
+                    TokenType::BANG_EQUAL => Expr::Bool(a != b),
+                    _ => Expr::Nil,
+                });
+            }
+        };
+
+        let overflow = || self.error_at(operator, "Integer overflow.");
+        Ok(match operator.token_type {
+            TokenType::PLUS => Expr::Int(a.checked_add(b).ok_or_else(overflow)?),
+            TokenType::MINUS => Expr::Int(a.checked_sub(b).ok_or_else(overflow)?),
+            TokenType::STAR => Expr::Int(a.checked_mul(b).ok_or_else(overflow)?),
+            TokenType::SLASH if b == 0 => return Err(self.error_at(operator, "Division by zero.")),
+            TokenType::SLASH => Expr::Int(a.checked_div(b).ok_or_else(overflow)?),
+            TokenType::GREATER => Expr::Bool(a > b),
+            TokenType::GREATER_EQUAL => Expr::Bool(a >= b),
+            TokenType::LESS => Expr::Bool(a < b),
+            TokenType::LESS_EQUAL => Expr::Bool(a <= b),
+            TokenType::EQUAL_EQUAL => Expr::Bool(a == b),
+            TokenType::BANG_EQUAL => Expr::Bool(a != b),
             _ => Expr::Nil,
+        })
+    }
+
+    fn position(&self, index: &Expr, bracket: &Token) -> Result<usize, RuntimeError> {
+        match index {
+            Expr::Number(n) if n.fract() == 0.0 && *n >= 0.0 => Ok(*n as usize),
+            Expr::Int(n) if *n >= 0 => Ok(*n as usize),
+            _ => Err(self.error_at(bracket, "Index must be a non-negative integer.")),
         }
     }
 
-    fn is_equal(&self, left: Expr, right: Expr) -> bool {
-        if left == right {
-            return true;
+    fn out_of_bounds(&self, position: usize, bracket: &Token) -> RuntimeError {
+        self.error_at(bracket, &format!("Index {} is out of bounds.", position))
+    }
+
+    // Functions and modules are only equal to themselves, which is decided by the scope they
+    // hold instead of comparing their bodies and environments. Arrays and objects are equal when
+    // their elements are
+    pub(crate) fn is_equal(&self, left: &Expr, right: &Expr) -> bool {
+        self.equal_values(left, right, &mut Vec::new())
+    }
+
+    // `comparing` holds the pairs of arrays and objects being compared further out, meeting one
+    // again means both sides loop back the same way, so that pair is taken as equal
+    fn equal_values(
+        &self,
+        left: &Expr,
+        right: &Expr,
+        comparing: &mut Vec<(*const (), *const ())>,
+    ) -> bool {
+        match (left, right) {
+            (Expr::Array(a), Expr::Array(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return true;
+                }
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                if comparing.contains(&pair) {
+                    return true;
+                }
+
+                comparing.push(pair);
+                let (a, b) = (a.borrow(), b.borrow());
+                let equal = a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(a, b)| self.equal_values(a, b, comparing));
+                comparing.pop();
+                equal
+            }
+            (Expr::Object(a), Expr::Object(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return true;
+                }
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                if comparing.contains(&pair) {
+                    return true;
+                }
+
+                comparing.push(pair);
+                let (a, b) = (a.borrow(), b.borrow());
+                let equal = a.len() == b.len()
+                    && a.iter().zip(b.iter()).all(|((a_name, a), (b_name, b))| {
+                        a_name == b_name && self.equal_values(a, b, comparing)
+                    });
+                comparing.pop();
+                equal
+            }
+            (
+                Expr::Function {
+                    name: left_name,
+                    environment: left_environment,
+                    ..
+                },
+                Expr::Function {
+                    name: right_name,
+                    environment: right_environment,
+                    ..
+                },
+            ) => {
+                left_name == right_name
+                    && match (left_environment, right_environment) {
+                        (Some(left), Some(right)) => Rc::ptr_eq(left, right),
+                        _ => false,
+                    }
+            }
+            (
+                Expr::Module {
+                    environment: left, ..
+                },
+                Expr::Module {
+                    environment: right, ..
+                },
+            ) => Rc::ptr_eq(left, right),
+            (Expr::Int(_), Expr::Number(_)) | (Expr::Number(_), Expr::Int(_)) => {
+                left.as_number() == right.as_number()
+            }
+            (Expr::Function { .. } | Expr::Module { .. } | Expr::Array(_) | Expr::Object(_), _)
+            | (_, Expr::Function { .. } | Expr::Module { .. } | Expr::Array(_) | Expr::Object(_)) => {
+                false
+            }
+            _ => left == right,
         }
-        return false;
+    }
+}
+
+fn field<'a>(fields: &'a [(String, Expr)], name: &str) -> Option<&'a Expr> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| value)
+}
+
+// Type of a runtime value as written in `match` patterns
+fn type_name(value: &EvaluatorReturn) -> &'static str {
+    match value {
+        EvaluatorReturn::Expr(e) => match e {
+            Expr::Number(_) | Expr::Int(_) => "number",
+            Expr::String(_) => "string",
+            Expr::Bool(_) => "bool",
+            Expr::Function { .. } => "function",
+            Expr::Module { .. } => "module",
+            Expr::Array(_) => "array",
+            Expr::Object(_) => "object",
+            _ => "nil",
+        },
+        EvaluatorReturn::Global(_) => "function",
+    }
+}
+
+// A native that is stored, returned or tested is kept as a value, the same as `evaluate_value`
+// does, instead of being an error or nil
+fn stored(value: EvaluatorReturn) -> Expr {
+    match value {
+        EvaluatorReturn::Expr(e) => e,
+        EvaluatorReturn::Global(native) => Expr::Native(native),
+    }
+}
+
+fn canonical(file: &Path) -> PathBuf {
+    fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf())
+}
+
+fn literal_value(literal: &Literal) -> Expr {
+    match literal {
+        Literal::Bool(b) => Expr::Bool(*b),
+        Literal::String(s) => Expr::String(s.clone()),
+        Literal::Number(n) => Expr::Number(n.0),
+        Literal::Nil => Expr::Nil,
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,211 @@
+use crate::error::ScanError;
+use crate::scanner::{self, Scanner};
+use crate::{Token, TokenType};
+
+const INDENT: &str = "  ";
+
+// Lays source out again from its tokens: one statement per line, two spaces of indentation per
+// block, single spaces around operators and at most one blank line in a row. Comments are kept
+// and put on their own line in front of whatever follows them
+pub fn format_source(source: &str) -> Result<String, ScanError> {
+    let mut tokens = Vec::new();
+    for token in Scanner::new().keeping_comments().iter(source) {
+        tokens.push(token?);
+    }
+
+    let mut formatter = Formatter::default();
+    for (index, token) in tokens.iter().enumerate() {
+        formatter.token(token, tokens.get(index + 1));
+    }
+    Ok(formatter.finish())
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    indent: usize,
+    // Inside parentheses a `;` belongs to a for loop header and doesn't end the line
+    parens: usize,
+    at_line_start: bool,
+    previous: Option<Token>,
+    // A `-` or `!` that applies to what follows it, which is written without a space
+    after_unary: bool,
+    // For each `{` still open, whether it starts an object literal that stays on one line
+    objects: Vec<bool>, // How many `{` were open at each `?` still waiting for its `:`, any other `:` follows the name
+    // of a field
+    questions: Vec<usize>,
+}
+
+impl Formatter {
+    fn token(&mut self, token: &Token, next: Option<&Token>) {
+        if token.token_type == TokenType::EOF {
+            return;
+        }
+
+        if !self.out.is_empty() && !self.at_line_start && token.token_type == TokenType::COMMENT {
+            self.newline();
+        }
+
+        let object = match token.token_type {
+            TokenType::LEFT_BRACE => {
+                let object = self.previous.as_ref().is_some_and(starts_object);
+                self.objects.push(object);
+                object
+            }
+            TokenType::RIGHT_BRACE => self.objects.pop().unwrap_or(false),
+            _ => false,
+        };
+        let field_colon = token.token_type == TokenType::COLON
+            && self.questions.last() != Some(&self.objects.len());
+        match token.token_type {
+            TokenType::QUESTION => self.questions.push(self.objects.len()),
+            TokenType::COLON if !field_colon => {
+                self.questions.pop();
+            }
+            _ => {}
+        }
+
+        if self.out.is_empty() || self.at_line_start {
+            if token.token_type == TokenType::RIGHT_BRACE {
+                self.indent = self.indent.saturating_sub(1);
+            }
+            if !self.out.is_empty() && self.blank_line_before(token) {
+                self.out.push('\n');
+            }
+            self.out.push_str(&INDENT.repeat(self.indent));
+        } else if !field_colon && self.space_before(token, object) {
+            self.out.push(' ');
+        }
+
+        // Trailing spaces of a line comment are part of its lexeme
+        self.out.push_str(match token.token_type {
+            TokenType::COMMENT => token.lexeme.trim_end(),
+            _ => &token.lexeme,
+        });
+        self.at_line_start = false;
+        self.after_unary = matches!(token.token_type, TokenType::MINUS | TokenType::BANG)
+            && !self.previous.as_ref().is_some_and(ends_operand);
+
+        match token.token_type {
+            TokenType::COMMENT => self.newline(),
+            TokenType::LEFT_PAREN => self.parens += 1,
+            TokenType::RIGHT_PAREN => self.parens = self.parens.saturating_sub(1),
+            TokenType::SEMICOLON if self.parens == 0 => self.newline(),
+            TokenType::LEFT_BRACE | TokenType::RIGHT_BRACE if object => {}
+            // An empty block stays `{}`
+            TokenType::LEFT_BRACE
+                if !next.is_some_and(|next| next.token_type == TokenType::RIGHT_BRACE) =>
+            {
+                self.indent += 1;
+                self.newline();
+            }
+            // `} else {` stays on one line
+            TokenType::RIGHT_BRACE
+                if !next.is_some_and(|next| {
+                    matches!(next.token_type, TokenType::ELSE | TokenType::SEMICOLON)
+                }) =>
+            {
+                self.newline()
+            }
+            _ => {}
+        }
+        self.previous = Some(token.clone());
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.at_line_start = true;
+    }
+
+    // Keeps one empty line where the source had one or more between two tokens
+    fn blank_line_before(&self, token: &Token) -> bool {
+        let start_line = token.line - scanner::line_breaks(&token.lexeme);
+        self.previous
+            .as_ref()
+            .is_some_and(|previous| start_line > previous.line + 1)
+    }
+
+    fn space_before(&self, token: &Token, object: bool) -> bool {
+        let previous = match &self.previous {
+            Some(previous) => previous,
+            None => return false,
+        };
+        if self.after_unary {
+            return false;
+        }
+        // `{name: value}` like `[1, 2]`, without spaces inside the braces
+        if (object && token.token_type == TokenType::RIGHT_BRACE)
+            || (previous.token_type == TokenType::LEFT_BRACE && self.objects.last() == Some(&true))
+        {
+            return false;
+        }
+
+        match (previous.token_type, token.token_type) {
+            (
+                TokenType::LEFT_PAREN
+                | TokenType::LEFT_BRACKET
+                | TokenType::DOT
+                | TokenType::QUESTION_DOT,
+                _,
+            )
+            | (TokenType::LEFT_BRACE, TokenType::RIGHT_BRACE) => return false,
+            _ => {}
+        }
+
+        match token.token_type {
+            TokenType::RIGHT_PAREN
+            | TokenType::RIGHT_BRACKET
+            | TokenType::SEMICOLON
+            | TokenType::COMMA
+            | TokenType::DOT
+            | TokenType::QUESTION_DOT => false,
+            // Calls and indexing stick to what they apply to, `if (` and `[1, 2]` don't
+            TokenType::LEFT_PAREN | TokenType::LEFT_BRACKET => !ends_operand(previous),
+            _ => true,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.out.is_empty() {
+            return self.out;
+        }
+        while self.out.ends_with('\n') {
+            self.out.pop();
+        }
+        self.out.push('\n');
+        self.out
+    }
+}
+
+fn ends_operand(token: &Token) -> bool {
+    matches!(
+        token.token_type,
+        TokenType::IDENTIFIER
+            | TokenType::NUMBER
+            | TokenType::STRING
+            | TokenType::RIGHT_PAREN
+            | TokenType::RIGHT_BRACKET
+            | TokenType::TRUE
+            | TokenType::FALSE
+            | TokenType::NIL
+            | TokenType::THIS
+    )
+}
+
+// A `{` after these is an object literal, after anything else it opens a block
+fn starts_object(token: &Token) -> bool {
+    matches!(
+        token.token_type,
+        TokenType::EQUAL
+            | TokenType::EQUAL_EQUAL
+            | TokenType::BANG_EQUAL
+            | TokenType::LEFT_PAREN
+            | TokenType::LEFT_BRACKET
+            | TokenType::COMMA
+            | TokenType::COLON
+            | TokenType::QUESTION
+            | TokenType::QUESTION_QUESTION
+            | TokenType::RETURN
+            | TokenType::PRINT
+    )
+}
@@ -1,80 +1,220 @@
-use crate::interpreter::{Expr, Literal};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::interpreter::{EvaluatorReturn, Expr, Literal, TokenType};
+
+// How many levels the AST printers and `print` go into an expression or value before writing
+// `...` instead, so a deeply nested one can't overflow the stack while being printed
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(200);
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn set_max_depth(depth: usize) {
+    MAX_DEPTH.store(depth, Ordering::Relaxed);
+}
+
+// Runs `print` one level deeper, or gives None once the maximum depth is reached
+pub fn nested<T>(print: impl FnOnce() -> T) -> Option<T> {
+    let depth = DEPTH.with(Cell::get);
+    if depth >= MAX_DEPTH.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    DEPTH.with(|current| current.set(depth + 1));
+    let printed = print();
+    DEPTH.with(|current| current.set(depth));
+    Some(printed)
+}
+
+// Strings are shown three ways: `tokenize` prints the raw value, `print` prints the content
+// without quotes and the AST (`Display for Expr`) quotes it with escapes, e.g. "hi\tthere"
 pub fn print_based_on_literal(literal: &Literal) -> String {
     match literal {
-        Literal::String(s) => format!("{s}"),
-        Literal::Number(f) => {
-            if (f.0 % 1.0).abs() < f64::EPSILON {
-                f.0.to_string() + ".0"
-            } else {
-                f.0.to_string()
-            }
-        }
+        Literal::String(s) => s.to_string(),
+        Literal::Number(f) => format_number_literal(f.0),
         Literal::Bool(b) => b.to_string(),
-        Literal::Null => String::from("null"),
         Literal::Nil => String::from("nil"),
     }
 }
 
-pub fn handle_grouping(exprs: &Vec<Expr>, left_side: &String, right_side: &String) -> Vec<String> {
-    let mut r: Vec<String> = vec![];
-    for e in exprs {
-        r.push(handle_match(e, left_side, right_side));
+// Numbers have three spellings: the token lexeme keeps the source text (`1.50`), literals
+// in tokenize and parse output always show a fraction (`1.5`, `100.0`) and runtime values
+// drop it for integers (`1.5`, `100`)
+pub fn format_number_literal(n: f64) -> String {
+    if n.fract() == 0.0 {
+        format!("{n}.0")
+    } else {
+        n.to_string()
     }
-    r
 }
 
-pub fn handle_match(expr: &Expr, left_side: &String, right_side: &String) -> String {
-    match expr {
-        Expr::Grouping(exprs) => {
-            format!(
-                "{left_side}{}{right_side}",
-                handle_grouping(exprs, &format!("(group "), &format!(")")).join(" ")
-            )
+// Runtime numbers are rounded to 15 significant digits, the most an f64 always keeps, and
+// then printed as short as possible. So `0.1 + 0.2` prints `0.3` instead of the
+// `0.30000000000000004` it is stored as
+pub fn format_number(n: f64) -> String {
+    if !n.is_finite() {
+        return n.to_string();
+    }
+    let rounded: f64 = format!("{:.14e}", n).parse().unwrap_or(n);
+    rounded.to_string()
+}
+
+pub fn quote_string(s: &str) -> String {
+    format!("{s:?}")
+}
+
+pub fn ast_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::String(s) => quote_string(s),
+        _ => print_based_on_literal(literal),
+    }
+}
+
+// Writes the numbers a program prints, in place of `format_number`. Integers made with
+// `--integers` are still printed as they are
+pub type NumberFormatter = Rc<dyn Fn(f64) -> String>;
+
+// How a runtime value is shown by `print` and by the `evaluate` command
+pub fn stringify(value: &EvaluatorReturn) -> String {
+    stringify_with(value, &format_number)
+}
+
+pub fn stringify_with(value: &EvaluatorReturn, number: &dyn Fn(f64) -> String) -> String {
+    match value {
+        EvaluatorReturn::Expr(e) => stringify_value(e, number, &mut Vec::new()),
+        EvaluatorReturn::Global(g) => g.to_string(),
+    }
+}
+
+// `printing` holds the arrays and objects being printed further out, one holding itself is shown
+// as `[...]` or `{...}` at that point instead of being printed forever. The same array twice side
+// by side is not a cycle and is printed both times. Past the maximum depth the contents are
+// written as `...` too, like the AST printers do
+fn stringify_value(
+    value: &Expr,
+    number: &dyn Fn(f64) -> String,
+    printing: &mut Vec<*const ()>,
+) -> String {
+    match value {
+        Expr::Function { name, .. } => format!("<fn {}>", name.lexeme),
+        Expr::Module { name, .. } => format!("<module {}>", name),
+        Expr::Native(native) => native.to_string(),
+        Expr::Array(elements) => {
+            let array = Rc::as_ptr(elements) as *const ();
+            if printing.contains(&array) {
+                return String::from("[...]");
+            }
+
+            printing.push(array);
+            let printed = nested(|| {
+                elements
+                    .borrow()
+                    .iter()
+                    .map(|element| stringify_value(element, number, printing))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            })
+            .unwrap_or_else(|| String::from("..."));
+            printing.pop();
+            format!("[{printed}]")
         }
-        Expr::Number(n) => {
-            format!("{left_side}{n}{right_side}")
+        Expr::Object(fields) => {
+            let object = Rc::as_ptr(fields) as *const ();
+            if printing.contains(&object) {
+                return String::from("{...}");
+            }
+
+            printing.push(object);
+            let printed = nested(|| {
+                fields
+                    .borrow()
+                    .iter()
+                    .map(|(name, value)| {
+                        format!("{name}: {}", stringify_value(value, number, printing))
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            })
+            .unwrap_or_else(|| String::from("..."));
+            printing.pop();
+            format!("{{{printed}}}")
         }
+        Expr::String(s) => s.to_string(),
+        Expr::Number(n) => number(*n),
+        Expr::Int(n) => n.to_string(),
+        Expr::Bool(b) => b.to_string(),
+        Expr::Nil => String::from("nil"),
+        Expr::Literal(Literal::String(s)) => s.to_string(),
+        Expr::Literal(Literal::Number(n)) => number(n.0),
+        Expr::Literal(Literal::Bool(b)) => b.to_string(),
+        Expr::Literal(Literal::Nil) => String::from("nil"),
+        _ => String::from("Invalid expression"),
+    }
+}
+
+// The fully parenthesized form `parse` prints, `1 + 2 * 3` is `(+ 1.0 (* 2.0 3.0))`. Every level
+// goes through here so nested expressions look the same as the outermost one
+pub fn ast_to_string(expr: &Expr) -> String {
+    nested(|| ast_node(expr)).unwrap_or_else(|| String::from("..."))
+}
+
+fn ast_node(expr: &Expr) -> String {
+    match expr {
+        Expr::Grouping(expr) => format!("(group {})", ast_to_string(expr)),
         Expr::Binary {
             operator,
             left,
             right,
-        } => {
+        } => format!(
+            "({} {} {})",
+            operator.lexeme,
+            ast_to_string(left),
+            ast_to_string(right)
+        ),
+        Expr::Logical(left, right, operator) => {
+            let operator = match operator {
+                TokenType::AND => "and",
+                TokenType::OR => "or",
+                _ => "??",
+            };
             format!(
-                "{left_side}({} {} {}){right_side}",
-                operator.lexeme,
-                handle_match(left, &String::from(""), &String::from("")),
-                handle_match(right, &String::from(""), &String::from(""))
+                "({operator} {} {})",
+                ast_to_string(left),
+                ast_to_string(right)
             )
         }
-        Expr::Literal(l) => {
-            format!("{left_side}{}{right_side}", print_based_on_literal(&l))
-        }
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => format!(
+            "(? {} {} {})",
+            ast_to_string(condition),
+            ast_to_string(then_branch),
+            ast_to_string(else_branch)
+        ),
         Expr::Unary { operator, right } => {
-            format!(
-                "{left_side}({} {}){right_side}",
-                operator.lexeme,
-                handle_match(right, &String::from(""), &String::from(""))
-            )
+            format!("({} {})", operator.lexeme, ast_to_string(right))
         }
-        Expr::String(s) => {
-            format!("{left_side}{s}{right_side}")
+        Expr::Assign { name, value } => {
+            format!("(= {} {})", name.lexeme, ast_to_string(value))
         }
-        Expr::Nil => {
-            format!("{left_side}nil{right_side}")
-        }
-        _ => {
-            format!("Invalid expression")
+        Expr::Call(callee, _, arguments) => {
+            let mut printed = format!("(call {}", ast_to_string(callee));
+            for argument in arguments {
+                printed.push(' ');
+                printed.push_str(&ast_to_string(argument));
+            }
+            printed.push(')');
+            printed
         }
-    }
-}
-
-pub fn get_from_unary(expr_unary: &Expr) -> String {
-    if let Expr::Unary { operator, right } = expr_unary {
-        format!("({} {})", operator.lexeme, get_from_unary(right))
-    } else if let Expr::Literal(literal) = expr_unary {
-        print_based_on_literal(&literal)
-    } else {
-        expr_unary.to_string()
+        Expr::Literal(literal) => print_based_on_literal(literal),
+        Expr::Number(n) => format_number_literal(*n),
+        Expr::String(s) => s.to_string(),
+        Expr::Nil => String::from("nil"),
+        _ => expr.to_string(),
     }
 }
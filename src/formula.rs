@@ -0,0 +1,73 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
+
+use crate::environment::{Environment, EnvironmentValue};
+use crate::error::FormulaError;
+use crate::evaluator::Evaluator;
+use crate::interpreter::EvaluatorReturn;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::visitor::{walk_expr, Visitor};
+use crate::Expr;
+
+// Evaluates one expression such as `a + b * 2` with the given variables and nothing else in
+// scope, for formula fields of a host program. Statements, assignments and calls are rejected,
+// so a formula can only compute a value from its inputs
+pub fn evaluate(source: &str, variables: &HashMap<String, Expr>) -> Result<Expr, FormulaError> {
+    let mut tokens = Vec::new();
+    for token in Scanner::new().iter(source) {
+        tokens.push(token?);
+    }
+    let expression = Parser::new(tokens).single_expression()?;
+
+    let mut check = PureCheck::default();
+    check.visit_expr(&expression);
+    if let Some(error) = check.error {
+        return Err(error);
+    }
+
+    let environment = Environment::new();
+    for (name, value) in variables {
+        environment.define(name, EnvironmentValue::Expr(value.clone()));
+    }
+
+    let evaluator = Evaluator::with_output(Rc::new(RefCell::new(io::sink())));
+    match evaluator.evaluate(&expression, &Rc::new(RefCell::new(environment)), None)? {
+        EvaluatorReturn::Expr(value) => Ok(value),
+        EvaluatorReturn::Global(_) => Ok(Expr::Nil),
+    }
+}
+
+#[derive(Default)]
+struct PureCheck {
+    error: Option<FormulaError>,
+}
+
+impl Visitor for PureCheck {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if self.error.is_some() {
+            return;
+        }
+        match expr {
+            Expr::Assign { name, .. } => {
+                self.error = Some(FormulaError::Rejected(format!(
+                    "Can't assign to '{}' in a formula.",
+                    name.lexeme
+                )))
+            }
+            Expr::Set { .. } | Expr::IndexSet { .. } => {
+                self.error = Some(FormulaError::Rejected(String::from(
+                    "Can't assign in a formula.",
+                )))
+            }
+            Expr::Call(..) => {
+                self.error = Some(FormulaError::Rejected(String::from(
+                    "Can't call functions in a formula.",
+                )))
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+}
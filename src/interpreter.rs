@@ -1,23 +1,35 @@
 use core::fmt;
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt::Debug;
-use std::io::Write;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{collections::HashMap, fs, io, process::exit, sync::Mutex};
+use std::time::{Duration, Instant};
+use std::{collections::HashMap, fs};
 
 use once_cell::sync::Lazy;
 
+use crate::cache::{ParseCache, ParseOptions};
+use crate::diagnostics::{self, Diagnostic, DiagnosticLevel};
 use crate::environment::EnvironmentValue;
-use crate::formatters::{get_from_unary, handle_grouping, handle_match, print_based_on_literal};
-use crate::{environment, evaluator, parser, runner, scanner};
+use crate::error::{LoxError, ParseError, RuntimeError};
+use crate::evaluator::BindingPolicy;
+use crate::formatters::{
+    ast_literal, ast_to_string, nested, print_based_on_literal, quote_string, stringify,
+    stringify_with, NumberFormatter,
+};
+use crate::natives::{
+    Apply, ApproxEq, Arity, AssertEq, Clock, Exit, Filter, FloorDiv, FormatNumber, Insert, Map,
+    Mod, Name, Num, Pop, Push, ReadAll, Reduce, Remove, Sleep, Sort, ToBool, ToNumber,
+};
+use crate::{environment, evaluator, format, parser, runner, scanner};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     String(String),
     Number((f64, usize)),
     Bool(bool),
-    Null,
     Nil,
 }
 
@@ -33,17 +45,25 @@ impl fmt::Display for TokenType {
     }
 }
 
+// Variant names are printed verbatim by `tokenize`, so they follow the reference token names.
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TokenType {
     LEFT_PAREN,
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
     COMMA,
     DOT,
     MINUS,
     PLUS,
     SEMICOLON,
+    COLON,
+    QUESTION,
+    QUESTION_QUESTION,
+    QUESTION_DOT,
     SLASH,
     STAR,
 
@@ -55,18 +75,23 @@ pub enum TokenType {
     GREATER_EQUAL,
     LESS,
     LESS_EQUAL,
+    ARROW,
 
     IDENTIFIER,
     STRING,
     NUMBER,
 
     AND,
+    BREAK,
     CLASS,
+    DO,
     ELSE,
     FALSE,
     FUN,
     FOR,
     IF,
+    IMPORT,
+    MATCH,
     NIL,
     OR,
     PRINT,
@@ -77,14 +102,20 @@ pub enum TokenType {
     VAR,
     WHILE,
 
+    // Only produced when the scanner keeps comments
+    COMMENT,
     EOF,
 }
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
+    // Only NUMBER and STRING tokens carry a literal, `tokenize` prints `null` for the rest
     pub literal: Option<Literal>,
     pub line: u32,
+    // Filled in by the scanner, tokens made elsewhere start at column 0
+    pub column: u32,
+    pub span: Span,
 }
 
 impl Token {
@@ -94,10 +125,19 @@ impl Token {
             lexeme,
             literal,
             line,
+            column: 0,
+            span: Span::default(),
         }
     }
 }
 
+// Character offsets into the source, `end` is one past the last character
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     Bool(bool),
@@ -105,30 +145,104 @@ pub enum Expr {
     Literal(Literal),
     Print(Box<Expr>),
     Return(Token, Box<Expr>),
+    // Leaves the innermost loop, the parser only accepts it inside one
+    Break(Token),
     Function {
         name: Token,
         params: Vec<Token>,
         body: Vec<Expr>,
         environment: Option<Rc<RefCell<environment::Environment>>>,
     },
-    Variable {
+    // `var name = value;`
+    VarDecl {
+        name: String,
+        value: Box<Expr>,
+    },
+    // `global name = value;` sets the variable in the outermost scope from anywhere, `global name;`
+    // creates it as nil unless it is already there
+    GlobalDecl {
         name: String,
         value: Box<Expr>,
     },
+    // `var x, y = value;` declares each name with one element of the array `value` gives
+    Destructure {
+        names: Vec<Token>,
+        value: Box<Expr>,
+    },
     Block(Vec<Expr>),
+    Import {
+        keyword: Token,
+        path: String,
+        alias: Option<Token>,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+        // Written `?.`, a nil object gives nil instead of an error
+        optional: bool,
+    },
+    // `[a, b]` as written, evaluating it makes an Expr::Array
+    List(Vec<Expr>),
+    // Arrays are shared, every variable holding one sees the same elements
+    Array(Rc<RefCell<Vec<Expr>>>),
+    // `{name: a, age: b}` as written, evaluating it makes an Expr::Object
+    ObjectLiteral(Vec<(Token, Expr)>),
+    // Fields keep the order they were written or added in and are shared like array elements
+    Object(Rc<RefCell<Vec<(String, Expr)>>>),
+    Index {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+    },
+    IndexSet {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
+    Match {
+        subject: Box<Expr>,
+        arms: Vec<(Pattern, Expr)>,
+        fallback: Option<Box<Expr>>,
+    },
+    // Declarations of a file imported with `as`, kept out of the global scope
+    Module {
+        name: String,
+        environment: Rc<RefCell<environment::Environment>>,
+        // Members can be read but not assigned, only `builtins` is made this way
+        read_only: bool,
+    },
+    // A native function passed as an argument, e.g. `arity(clock)`
+    Native(Global),
     While(Box<Expr>, Box<Expr>),
-    Var(Token),
+    // The body of a `for` loop declaring `names`, each pass gets its own copy of them so a
+    // closure made in the body keeps the values of that pass. Changes are copied back to the
+    // loop's variables for the increment and condition
+    Iteration {
+        names: Vec<String>,
+        body: Box<Expr>,
+    },
+    // `do body while (condition);`, the body runs once before the condition is checked
+    DoWhile(Box<Expr>, Box<Expr>),
+    // A variable read by name
+    VarRef(Token),
     If {
         condition: Box<Expr>,
         then_branch: Box<Expr>,
         else_branch: Option<Box<Expr>>,
     },
     Assign {
-        name: String,
+        name: Token,
         value: Box<Expr>,
     },
-    Increment(Box<Expr>),
     Number(f64),
+    // Only made from integer literals with `--integers`, see `Evaluator::integer_binary`
+    Int(i64),
     Nil,
     String(String),
     Unary {
@@ -140,26 +254,83 @@ pub enum Expr {
         right: Box<Expr>,
         left: Box<Expr>,
     },
-    Grouping(Vec<Expr>),
+    // `condition ? then_branch : else_branch`, only the chosen branch is evaluated
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+    // A parenthesized expression, `(a, b)` is not a thing in Lox
+    Grouping(Box<Expr>),
     Call(Box<Expr>, Token, Vec<Expr>),
 }
 
-impl<'a> fmt::Display for Expr {
+impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match nested(|| self.fmt_node(f)) {
+            Some(result) => result,
+            None => f.write_str("..."),
+        }
+    }
+}
+
+impl Expr {
+    fn fmt_node(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Expr::Return(keyword, value) => f.write_fmt(format_args!("{keyword} {value}")),
             Expr::Function {
                 name,
                 params,
                 body,
-                environment
-            } => f.write_fmt(format_args!(
-                "{name} {:?} {:?}",
-                params, body
-            )),
+                environment: _,
+            } => f.write_fmt(format_args!("{name} {:?} {:?}", params, body)),
             Expr::Call(a, b, c) => f.write_fmt(format_args!("{a} {b} {:?}", c)),
-            Expr::Increment(a) => f.write_fmt(format_args!("{a}")),
             Expr::While(a, b) => f.write_fmt(format_args!("{a} {b}")),
+            Expr::DoWhile(body, condition) => {
+                f.write_fmt(format_args!("do {body} while {condition}"))
+            }
+            Expr::Iteration { body, .. } => f.write_fmt(format_args!("{body}")),
+            Expr::Break(keyword) => f.write_fmt(format_args!("{keyword}")),
+            Expr::Import { path, alias, .. } => match alias {
+                Some(alias) => f.write_fmt(format_args!("import {path:?} as {}", alias.lexeme)),
+                None => f.write_fmt(format_args!("import {path:?}")),
+            },
+            Expr::Get {
+                object,
+                name,
+                optional,
+            } => {
+                let dot = if *optional { "?." } else { "." };
+                f.write_fmt(format_args!("{object}{dot}{}", name.lexeme))
+            }
+            Expr::Module { name, .. } => f.write_fmt(format_args!("<module {name}>")),
+            Expr::Native(native) => f.write_fmt(format_args!("{native}")),
+            Expr::List(elements) => f.write_fmt(format_args!("[{}]", join(elements))),
+            Expr::Array(elements) => f.write_fmt(format_args!("[{}]", join(&elements.borrow()))),
+            Expr::ObjectLiteral(fields) => {
+                let fields = fields.iter().map(|(name, value)| (&name.lexeme, value));
+                f.write_fmt(format_args!("{{{}}}", join_fields(fields)))
+            }
+            Expr::Object(fields) => {
+                let fields = fields.borrow();
+                f.write_fmt(format_args!(
+                    "{{{}}}",
+                    join_fields(fields.iter().map(|(name, value)| (name, value)))
+                ))
+            }
+            Expr::Index { object, index, .. } => f.write_fmt(format_args!("{object}[{index}]")),
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => f.write_fmt(format_args!("{object}.{} = {value}", name.lexeme)),
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => f.write_fmt(format_args!("{object}[{index}] = {value}")),
+            Expr::Match { subject, .. } => f.write_fmt(format_args!("match {subject}")),
             Expr::Logical(a, b, c) => f.write_fmt(format_args!("{a} {b} {c}")),
             Expr::If {
                 condition,
@@ -177,15 +348,26 @@ impl<'a> fmt::Display for Expr {
                 }
                 Ok(())
             }
-            Expr::Assign { name, value } => f.write_fmt(format_args!("{name} = {value}")),
-            Expr::Var(expr) => f.write_fmt(format_args!("{expr}")),
-            Expr::Variable { name, value } => f.write_fmt(format_args!("{name} = {value}")),
+            Expr::Assign { name, value } => f.write_fmt(format_args!("{} = {value}", name.lexeme)),
+            Expr::VarRef(name) => f.write_str(&name.lexeme),
+            Expr::VarDecl { name, value } => f.write_fmt(format_args!("var {name} = {value}")),
+            Expr::GlobalDecl { name, value } => {
+                f.write_fmt(format_args!("global {name} = {value}"))
+            }
+            Expr::Destructure { names, value } => {
+                let names = names.iter().map(|name| name.lexeme.as_str());
+                f.write_fmt(format_args!(
+                    "var {} = {value}",
+                    names.collect::<Vec<_>>().join(", ")
+                ))
+            }
             Expr::Print(expr) => f.write_fmt(format_args!("{expr}")),
             Expr::Bool(b) => f.write_fmt(format_args!("{}", b)),
             Expr::Nil => f.write_str("nil"),
-            Expr::String(s) => f.write_fmt(format_args!("{s:?}")),
+            Expr::String(s) => f.write_str(&quote_string(s)),
             Expr::Number(n) => f.write_fmt(format_args!("{n:?}")),
-            Expr::Literal(l) => f.write_fmt(format_args!("{l:?}")),
+            Expr::Int(n) => f.write_fmt(format_args!("{n}")),
+            Expr::Literal(l) => f.write_str(&ast_literal(l)),
             Expr::Unary { operator, right } => {
                 f.write_fmt(format_args!("{} {right}", operator.lexeme))
             }
@@ -194,11 +376,38 @@ impl<'a> fmt::Display for Expr {
                 right,
                 left,
             } => f.write_fmt(format_args!("({} {left} {right}", operator.lexeme)),
-            Expr::Grouping(_) => f.write_str("()"),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => f.write_fmt(format_args!("{condition} ? {then_branch} : {else_branch}")),
+            Expr::Grouping(expr) => f.write_fmt(format_args!("({expr})")),
         }
     }
 }
 
+fn join(exprs: &[Expr]) -> String {
+    exprs
+        .iter()
+        .map(|expr| expr.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn join_fields<'a>(fields: impl Iterator<Item = (&'a String, &'a Expr)>) -> String {
+    fields
+        .map(|(name, value)| format!("{name}: {value}"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// One arm of a `match`, either a type name such as `number` or a literal value
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Type(String),
+    Value(Literal),
+}
+
 #[derive(Clone, Debug)]
 pub enum EvaluatorReturn {
     Expr(Expr),
@@ -208,24 +417,238 @@ pub enum EvaluatorReturn {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Global {
     Clock(Clock),
+    ToNumber(ToNumber),
+    ToBool(ToBool),
+    Exit(Exit),
+    FormatNumber(FormatNumber),
+    Num(Num),
+    FloorDiv(FloorDiv),
+    Mod(Mod),
+    AssertEq(AssertEq),
+    ApproxEq(ApproxEq),
+    Apply(Apply),
+    Sort(Sort),
+    Arity(Arity),
+    Name(Name),
+    ReadAll(ReadAll),
+    Push(Push),
+    Pop(Pop),
+    Insert(Insert),
+    Remove(Remove),
+    Map(Map),
+    Filter(Filter),
+    Reduce(Reduce),
+    Sleep(Sleep),
+}
+
+impl Global {
+    // The name it is defined under, even when a script has declared something else with it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Global::Clock(_) => "clock",
+            Global::ToNumber(_) => "to_number",
+            Global::ToBool(_) => "to_bool",
+            Global::Exit(_) => "exit",
+            Global::FormatNumber(_) => "format_number",
+            Global::Num(_) => "num",
+            Global::FloorDiv(_) => "floor_div",
+            Global::Mod(_) => "mod",
+            Global::AssertEq(_) => "assert_eq",
+            Global::ApproxEq(_) => "approx_eq",
+            Global::Apply(_) => "apply",
+            Global::Sort(_) => "sort",
+            Global::Arity(_) => "arity",
+            Global::Name(_) => "name",
+            Global::ReadAll(_) => "read_all",
+            Global::Push(_) => "push",
+            Global::Pop(_) => "pop",
+            Global::Insert(_) => "insert",
+            Global::Remove(_) => "remove",
+            Global::Map(_) => "map",
+            Global::Filter(_) => "filter",
+            Global::Reduce(_) => "reduce",
+            Global::Sleep(_) => "sleep",
+        }
+    }
+}
+
+impl LoxCallable for Global {
+    fn call(
+        &self,
+        evaluator: &evaluator::Evaluator,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        match self {
+            Global::Clock(c) => c.call(evaluator, environment, fn_bind, arguments),
+            Global::ToNumber(n) => n.call(evaluator, environment, fn_bind, arguments),
+            Global::ToBool(b) => b.call(evaluator, environment, fn_bind, arguments),
+            Global::Exit(e) => e.call(evaluator, environment, fn_bind, arguments),
+            Global::FormatNumber(f) => f.call(evaluator, environment, fn_bind, arguments),
+            Global::Num(n) => n.call(evaluator, environment, fn_bind, arguments),
+            Global::FloorDiv(f) => f.call(evaluator, environment, fn_bind, arguments),
+            Global::Mod(m) => m.call(evaluator, environment, fn_bind, arguments),
+            Global::AssertEq(a) => a.call(evaluator, environment, fn_bind, arguments),
+            Global::ApproxEq(a) => a.call(evaluator, environment, fn_bind, arguments),
+            Global::Apply(a) => a.call(evaluator, environment, fn_bind, arguments),
+            Global::Sort(s) => s.call(evaluator, environment, fn_bind, arguments),
+            Global::Arity(a) => a.call(evaluator, environment, fn_bind, arguments),
+            Global::Name(n) => n.call(evaluator, environment, fn_bind, arguments),
+            Global::ReadAll(r) => r.call(evaluator, environment, fn_bind, arguments),
+            Global::Push(p) => p.call(evaluator, environment, fn_bind, arguments),
+            Global::Pop(p) => p.call(evaluator, environment, fn_bind, arguments),
+            Global::Insert(i) => i.call(evaluator, environment, fn_bind, arguments),
+            Global::Remove(r) => r.call(evaluator, environment, fn_bind, arguments),
+            Global::Map(m) => m.call(evaluator, environment, fn_bind, arguments),
+            Global::Filter(f) => f.call(evaluator, environment, fn_bind, arguments),
+            Global::Reduce(r) => r.call(evaluator, environment, fn_bind, arguments),
+            Global::Sleep(s) => s.call(evaluator, environment, fn_bind, arguments),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Global::Clock(c) => c.arity(),
+            Global::ToNumber(n) => n.arity(),
+            Global::ToBool(b) => b.arity(),
+            Global::Exit(e) => e.arity(),
+            Global::FormatNumber(f) => f.arity(),
+            Global::Num(n) => n.arity(),
+            Global::FloorDiv(f) => f.arity(),
+            Global::Mod(m) => m.arity(),
+            Global::AssertEq(a) => a.arity(),
+            Global::ApproxEq(a) => a.arity(),
+            Global::Apply(a) => a.arity(),
+            Global::Sort(s) => s.arity(),
+            Global::Arity(a) => a.arity(),
+            Global::Name(n) => n.arity(),
+            Global::ReadAll(r) => r.arity(),
+            Global::Push(p) => p.arity(),
+            Global::Pop(p) => p.arity(),
+            Global::Insert(i) => i.arity(),
+            Global::Remove(r) => r.arity(),
+            Global::Map(m) => m.arity(),
+            Global::Filter(f) => f.arity(),
+            Global::Reduce(r) => r.arity(),
+            Global::Sleep(s) => s.arity(),
+        }
+    }
+
+    fn max_arity(&self) -> usize {
+        match self {
+            Global::Sort(s) => s.max_arity(),
+            _ => self.arity(),
+        }
+    }
+}
+
+impl fmt::Display for Global {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<native fn>")
+    }
 }
 
 pub trait LoxCallable: Debug + Clone {
     fn call(
         &self,
+        evaluator: &evaluator::Evaluator,
         environment: &Rc<RefCell<environment::Environment>>,
         fn_bind: Option<&Expr>,
         arguments: Vec<Expr>,
-    ) -> CallReturn;
+    ) -> Result<CallReturn, RuntimeError>;
     fn arity(&self) -> usize;
+    // Callables with optional trailing parameters accept anything from arity up to this
+    fn max_arity(&self) -> usize {
+        self.arity()
+    }
 }
 
 impl Expr {
-    pub fn is_lox_callable(&self, callee: &Expr) -> bool {
-        match &callee {
-            Expr::Var(_) => true,
-            Expr::Call(..) => true,
-            _ => false,
+    // Statements that are just an expression, `2 + 2;` or `f();`, rather than a declaration or
+    // a statement such as `print` or `if`
+    pub fn is_expression_statement(&self) -> bool {
+        !matches!(
+            self,
+            Expr::VarDecl { .. }
+                | Expr::GlobalDecl { .. }
+                | Expr::Destructure { .. }
+                | Expr::Function { .. }
+                | Expr::Import { .. }
+                | Expr::Print(_)
+                | Expr::Return(..)
+                | Expr::Break(_)
+                | Expr::Block(_)
+                | Expr::If { .. }
+                | Expr::While(..)
+                | Expr::DoWhile(..)
+                | Expr::Match { .. }
+        )
+    }
+
+    // Either kind of number as a float, for the places that don't keep integers
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Expr::Number(n) => Some(*n),
+            Expr::Int(n) => Some(*n as f64),
+            _ => None,
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Expr::Nil | Expr::Bool(false))
+    }
+
+    // Orders any two values: nil, then booleans, numbers, strings and arrays, and last everything
+    // else, which compares equal. Numbers go by value, strings lexicographically and arrays
+    // element by element
+    pub fn compare(&self, other: &Expr) -> Ordering {
+        self.compare_with(other, &mut Vec::new())
+    }
+
+    // `comparing` holds the pairs of arrays being compared further out, like in
+    // `Evaluator::is_equal`, an array holding itself doesn't recurse forever
+    fn compare_with(&self, other: &Expr, comparing: &mut Vec<(*const (), *const ())>) -> Ordering {
+        match (self, other) {
+            (Expr::Bool(a), Expr::Bool(b)) => a.cmp(b),
+            (Expr::Number(a), Expr::Number(b)) => {
+                a.partial_cmp(b).unwrap_or_else(|| a.total_cmp(b))
+            }
+            (Expr::Int(a), Expr::Int(b)) => a.cmp(b),
+            (Expr::Int(_), Expr::Number(_)) | (Expr::Number(_), Expr::Int(_)) => {
+                Expr::Number(self.as_number().unwrap())
+                    .compare(&Expr::Number(other.as_number().unwrap()))
+            }
+            (Expr::String(a), Expr::String(b)) => a.cmp(b),
+            (Expr::Array(a), Expr::Array(b)) => {
+                let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+                if Rc::ptr_eq(a, b) || comparing.contains(&pair) {
+                    return Ordering::Equal;
+                }
+
+                comparing.push(pair);
+                let (a, b) = (a.borrow(), b.borrow());
+                let ordering = a
+                    .iter()
+                    .zip(b.iter())
+                    .map(|(a, b)| a.compare_with(b, comparing))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or_else(|| a.len().cmp(&b.len()));
+                comparing.pop();
+                ordering
+            }
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+
+    fn type_rank(&self) -> u8 {
+        match self {
+            Expr::Nil => 0,
+            Expr::Bool(_) => 1,
+            Expr::Number(_) | Expr::Int(_) => 2,
+            Expr::String(_) => 3,
+            Expr::Array(_) => 4,
+            _ => 5,
         }
     }
 }
@@ -233,105 +656,82 @@ impl Expr {
 impl LoxCallable for Expr {
     fn call(
         &self,
-        _environment: &Rc<RefCell<environment::Environment>>,
-        _fn_bind: Option<&Expr>,
+        evaluator: &evaluator::Evaluator,
+        environment: &Rc<RefCell<environment::Environment>>,
+        fn_bind: Option<&Expr>,
         arguments: Vec<Expr>,
-    ) -> CallReturn {
-        // Don't have declaration
+    ) -> Result<CallReturn, RuntimeError> {
+        // A native passed around as a value, e.g. the callback of `map(strings, num)`
+        if let Expr::Native(native) = self {
+            return native.call(evaluator, environment, fn_bind, arguments);
+        }
 
-        match self {
-            Expr::Function { params, body, environment: env_fn, .. } => {
-                let fn_scope = Rc::new(RefCell::new(environment::Environment::new()));
-
-                for i in 0..params.len() {
-                    fn_scope.borrow().define(
-                        &params[i].lexeme,
-                        EnvironmentValue::Expr(arguments[i].clone()),
-                    );
-                }
+        if let Expr::Function {
+            params,
+            body,
+            environment: env_fn,
+            ..
+        } = self
+        {
+            // Every call gets its own scope for the parameters, they shadow names from the
+            // closure and assigning to them never reaches the captured environment
+            let fn_scope = Rc::new(RefCell::new(environment::Environment::new()));
 
-                fn_scope.borrow_mut().set_enclosing(env_fn.clone().unwrap());
+            for (param, argument) in params.iter().zip(arguments) {
+                fn_scope
+                    .borrow()
+                    .define(&param.lexeme, EnvironmentValue::Expr(argument));
+            }
 
-                let evaluator = evaluator::Evaluator::new();
-                let expr_block = Expr::Block(body.clone());
-                let evaluated =
-                    evaluator.evaluate(&expr_block, &fn_scope, Some(&expr_block));
+            fn_scope.borrow_mut().set_enclosing(env_fn.clone().unwrap());
 
+            let expr_block = Expr::Block(body.clone());
+            let evaluated = evaluator.evaluate(&expr_block, &fn_scope, Some(&expr_block))?;
 
-                if let EvaluatorReturn::Expr(e) = evaluated {
-                    match e {
-                        Expr::Return(_, v) => return CallReturn::Expr(*v),
-                        _ => return CallReturn::Expr(Expr::Nil),
-                    }
-                } else {
-                    return CallReturn::Expr(Expr::Nil);
-                }
+            if let EvaluatorReturn::Expr(Expr::Return(_, v)) = evaluated {
+                return Ok(CallReturn::Expr(*v));
+            } else {
+                return Ok(CallReturn::Expr(Expr::Nil));
             }
-            _ => {}
         }
 
-        CallReturn::Expr(Expr::String(format!("<fn Nil>")))
+        Ok(CallReturn::Expr(Expr::String("<fn Nil>".to_string())))
     }
 
     fn arity(&self) -> usize {
         match &self {
             Expr::Function { params, .. } => params.len(),
+            Expr::Native(native) => native.arity(),
             _ => 0,
         }
     }
-}
-
-pub enum CallReturn {
-    Expr(Expr),
-}
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Clock {}
-
-impl LoxCallable for Clock {
-    fn call(
-        &self,
-        _environment: &Rc<RefCell<environment::Environment>>,
-        _fn_bind: Option<&Expr>,
-        _arguments: Vec<Expr>,
-    ) -> CallReturn {
-        CallReturn::Expr(Expr::Number(
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs_f64(),
-        ))
-    }
-
-    fn arity(&self) -> usize {
-        0
+    fn max_arity(&self) -> usize {
+        match &self {
+            Expr::Native(native) => native.max_arity(),
+            _ => self.arity(),
+        }
     }
 }
 
-impl Clock {
-    pub fn new() -> Self {
-        Self {}
-    }
-
-    pub fn is_lox_callable(&self, _callee: &Expr) -> bool {
-        true
-    }
-
-    pub fn to_string(&self) -> String {
-        String::from("<native fn>")
-    }
+pub enum CallReturn {
+    Expr(Expr),
 }
 
-pub static RESERVED_KEYWORDS: Lazy<Mutex<HashMap<&'static str, TokenType>>> = Lazy::new(|| {
+pub static RESERVED_KEYWORDS: Lazy<HashMap<&'static str, TokenType>> = Lazy::new(|| {
     let mut map = HashMap::new();
 
     map.insert("and", TokenType::AND);
+    map.insert("break", TokenType::BREAK);
     map.insert("class", TokenType::CLASS);
+    map.insert("do", TokenType::DO);
     map.insert("else", TokenType::ELSE);
     map.insert("false", TokenType::FALSE);
     map.insert("for", TokenType::FOR);
     map.insert("fun", TokenType::FUN);
     map.insert("if", TokenType::IF);
+    map.insert("import", TokenType::IMPORT);
+    map.insert("match", TokenType::MATCH);
     map.insert("nil", TokenType::NIL);
     map.insert("or", TokenType::OR);
     map.insert("print", TokenType::PRINT);
@@ -342,7 +742,7 @@ pub static RESERVED_KEYWORDS: Lazy<Mutex<HashMap<&'static str, TokenType>>> = La
     map.insert("var", TokenType::VAR);
     map.insert("while", TokenType::WHILE);
 
-    Mutex::new(map)
+    map
 });
 
 impl fmt::Display for Token {
@@ -351,45 +751,128 @@ impl fmt::Display for Token {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timings {
+    pub scan: Duration,
+    pub parse: Duration,
+    pub evaluate: Duration,
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[time] scan: {:?}", self.scan)?;
+        writeln!(f, "[time] parse: {:?}", self.parse)?;
+        write!(f, "[time] evaluate: {:?}", self.evaluate)
+    }
+}
+
 pub struct Interpreter {
     pub file_contents: String,
+    pub exit_code: i32,
+    pub dump_env: bool,
+    pub show_warnings: bool,
+    // Parser warnings become errors that keep the program from running
+    pub strict: bool,
+    pub binding: BindingPolicy,
+    pub strict_variables: bool,
+    pub strict_booleans: bool,
+    // `print` can be used inside expressions and gives the value it printed
+    pub print_expressions: bool,
+    // Integer literals make integers instead of floats, see `Evaluator::integer_binary`
+    pub integers: bool,
+    // When the last statement of a program is a bare expression such as `2 + 2;`, `run` prints
+    // its value like a REPL would, nil is left out
+    pub print_result: bool,
+    // Prints how long each phase of `run` took to stderr
+    pub time: bool,
+    // `sleep` returns right away, see `Evaluator::with_sandbox`
+    pub sandbox: bool,
+    number_formatter: Option<NumberFormatter>,
+    timings: Timings,
+    path: Option<PathBuf>,
+    output: Rc<RefCell<dyn Write>>,
+    input: Rc<RefCell<dyn Read>>,
     expressions: Option<Vec<Expr>>,
+    parse_cache: Option<ParseCache>,
+    // Global scope of the last `run`, kept so `eval_more` can continue from it
+    globals: Option<Rc<RefCell<environment::Environment>>>,
 }
 
 impl Interpreter {
     pub fn new(filename: &str) -> Self {
         let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
-            writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
+            eprintln!("Failed to read file {}", filename);
             String::new()
         });
 
-        if file_contents.is_empty() {
-            println!("EOF  null");
-        }
+        let mut interpreter = Self::from_source(&file_contents);
+        interpreter.path = Some(PathBuf::from(filename));
+        interpreter
+    }
 
+    pub fn from_source(source: &str) -> Self {
         Self {
-            file_contents,
+            file_contents: source.to_string(),
+            exit_code: 0,
+            dump_env: false,
+            show_warnings: true,
+            strict: false,
+            binding: BindingPolicy::Late,
+            strict_variables: false,
+            strict_booleans: false,
+            print_expressions: false,
+            integers: false,
+            print_result: false,
+            time: false,
+            sandbox: false,
+            number_formatter: None,
+            timings: Timings::default(),
+            path: None,
+            parse_cache: None,
+            globals: None,
+            output: Rc::new(RefCell::new(io::stdout())),
+            input: Rc::new(RefCell::new(io::stdin())),
             expressions: None,
         }
     }
 
+    // Everything printed by `tokenize` and `run` goes here instead of stdout
+    pub fn set_output(&mut self, output: Rc<RefCell<dyn Write>>) {
+        self.output = output;
+    }
+
+    // What `read_all` reads during `run` instead of stdin
+    pub fn set_input(&mut self, input: Rc<RefCell<dyn Read>>) {
+        self.input = input;
+    }
+
+    // Numbers printed by `run` and `evaluate` are written by `formatter` instead of the default
+    // shortest spelling, e.g. with thousands separators or a fixed count of decimals
+    pub fn set_number_formatter(&mut self, formatter: impl Fn(f64) -> String + 'static) {
+        self.number_formatter = Some(Rc::new(formatter));
+    }
+
+    // One `TOKEN_TYPE lexeme literal` line per token as in Crafting Interpreters, an empty file
+    // still gives `EOF  null`. Scan errors go to stderr and the rest of the file is still listed
     pub fn tokenize(&mut self) {
-        if !self.file_contents.is_empty() {
-            let mut error_code: u8 = 0;
-            let mut scanner = scanner::Scanner::new();
-            scanner.scan_tokens(&self.file_contents, &mut error_code);
-            for v in scanner.tokens.iter() {
-                println!(
-                    "{} {} {}",
-                    v.token_type,
-                    v.lexeme,
-                    print_based_on_literal(&v.literal.as_ref().unwrap())
-                );
-            }
+        let mut error_code: u8 = 0;
+        let mut scanner = scanner::Scanner::new();
+        scanner.scan_tokens(&self.file_contents, &mut error_code);
+        for v in scanner.tokens.iter() {
+            writeln!(
+                self.output.borrow_mut(),
+                "{} {} {}",
+                v.token_type,
+                v.lexeme,
+                v.literal
+                    .as_ref()
+                    .map_or(String::from("null"), print_based_on_literal)
+            )
+            .unwrap();
+        }
 
-            if error_code == 65 {
-                exit(65);
-            }
+        if error_code == 65 {
+            self.exit_code = 65;
         }
     }
 
@@ -399,49 +882,13 @@ impl Interpreter {
             scanned.scan_tokens(&self.file_contents, &mut 0);
 
             let mut parser = parser::Parser::new(scanned.tokens);
-            let expressions = parser.expression();
+            let expressions = match parser.expression() {
+                Ok(expression) => expression,
+                Err(error) => return self.parse_errors(&[error]),
+            };
 
+            writeln!(self.output.borrow_mut(), "{}", ast_to_string(&expressions)).unwrap();
             self.expressions = Some(vec![expressions]);
-
-            match &self.expressions.as_ref().unwrap()[0] {
-                Expr::Grouping(exprs) => {
-                    println!(
-                        "{}",
-                        handle_grouping(exprs, &String::from("(group "), &String::from(")"))
-                            .join(" ")
-                    );
-                }
-                Expr::Number(n) => {
-                    println!("{n}");
-                }
-                Expr::Binary {
-                    operator,
-                    left,
-                    right,
-                } => {
-                    println!(
-                        "({} {} {})",
-                        operator.lexeme,
-                        handle_match(left, &String::from(""), &String::from("")),
-                        handle_match(right, &String::from(""), &String::from(""))
-                    );
-                }
-                Expr::Literal(l) => {
-                    println!("{}", print_based_on_literal(&l));
-                }
-                Expr::Unary { .. } => {
-                    println!("{}", get_from_unary(&self.expressions.as_ref().unwrap()[0]));
-                }
-                Expr::String(s) => {
-                    println!("{}", s);
-                }
-                Expr::Nil => {
-                    println!("nil");
-                }
-                _ => {
-                    print!("Invalid expression");
-                }
-            }
         }
     }
 
@@ -449,62 +896,359 @@ impl Interpreter {
         if !self.file_contents.is_empty() {
             let mut scanner = scanner::Scanner::new();
             scanner.scan_tokens(&self.file_contents, &mut 0);
-            let mut parser = parser::Parser::new(scanner.tokens);
-            let expression = parser.expression();
+            let mut parser = self.parser(scanner.tokens);
+            let expression = match parser.expression() {
+                Ok(expression) => expression,
+                Err(error) => return self.parse_errors(&[error]),
+            };
             let evaluator = evaluator::Evaluator::new();
-            match evaluator.evaluate(&expression, &Rc::new(RefCell::new(environment::Environment::new())), None) {
-                EvaluatorReturn::Expr(e) => match e {
-                    Expr::String(s) => {
-                        println!("{}", s);
-                    }
-                    Expr::Number(n) => {
-                        println!("{}", n);
-                    }
-                    Expr::Bool(b) => {
-                        println!("{}", b);
-                    }
-                    Expr::Nil => {
-                        println!("nil");
-                    }
-                    _ => {
-                        print!("Invalid expression");
-                    }
-                },
-                _ => {
-                    print!("Invalid expression");
-                }
+            match evaluator.evaluate(
+                &expression,
+                &Rc::new(RefCell::new(environment::Environment::new())),
+                None,
+            ) {
+                Ok(value) => runner::interpret(
+                    &mut *self.output.borrow_mut(),
+                    value,
+                    self.number_formatter.as_ref(),
+                ),
+                Err(error) => self.runtime_error(error),
+            }
+        }
+    }
+
+    // Writes the source back out laid out by `format::format_source`
+    pub fn format(&mut self) {
+        match format::format_source(&self.file_contents) {
+            Ok(formatted) => write!(self.output.borrow_mut(), "{}", formatted).unwrap(),
+            Err(error) => {
+                eprintln!("{}", error);
+                self.exit_code = 65;
             }
         }
     }
 
-    pub fn run(&self) {
+    // Scans and parses without running, errors and warnings are reported as `run` would and the
+    // exit code is 65 when there was an error. Mistakes only found at runtime pass
+    pub fn check(&mut self) {
+        let mut error_code = 0;
+        let mut scanner = scanner::Scanner::new();
+        scanner.scan_tokens(&self.file_contents, &mut error_code);
+        if self.parse_tokens(scanner.tokens).is_none() || error_code != 0 {
+            self.exit_code = 65;
+        }
+    }
+
+    // Scans and parses without running, every error is written as one JSON array
+    pub fn diagnostics(&mut self) {
+        let mut diagnostics = Vec::new();
+        let mut tokens = Vec::new();
+        let mut scanner = scanner::Scanner::new();
+        for result in scanner.iter(&self.file_contents) {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => diagnostics.push(Diagnostic::from(&error)),
+            }
+        }
+
+        let mut parser = self.parser(tokens);
+        parser.parse();
+        diagnostics.extend(parser.errors.iter().map(Diagnostic::from));
+        let level = self.warning_level();
+        diagnostics.extend(
+            parser
+                .warnings
+                .iter()
+                .map(|warning| warning.to_diagnostic(level)),
+        );
+
+        writeln!(
+            self.output.borrow_mut(),
+            "{}",
+            diagnostics::to_json(&diagnostics)
+        )
+        .unwrap();
+        if diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == "error")
+        {
+            self.exit_code = 65;
+        }
+    }
+
+    pub fn run(&mut self) {
+        self.run_with_env(Rc::new(RefCell::new(environment::Environment::globals())));
+    }
+
+    // Runs in globals prepared by the caller, which should include the natives if the script
+    // uses them, `Environment::globals` starts with them
+    pub fn run_with_env(&mut self, environment: Rc<RefCell<environment::Environment>>) {
         if !self.file_contents.is_empty() {
-            let mut scanner = scanner::Scanner::new();
-            scanner.scan_tokens(&self.file_contents, &mut 0);
-            let mut parser = parser::Parser::new(scanner.tokens);
-            parser.parse();
-            let evaluator = evaluator::Evaluator::new();
-            let environment = Rc::new(RefCell::new(environment::Environment::new()));
+            self.timings = Timings::default();
+            let statements = match self.parse_program(&self.file_contents.clone()) {
+                Some(statements) => statements,
+                None => return,
+            };
+            let evaluator = self.evaluator();
 
-            environment.borrow().define(
-                "clock",
-                EnvironmentValue::Global(Global::Clock(Clock::new())),
+            let started = Instant::now();
+            let last = match run_statements(&evaluator, &statements, &environment) {
+                Ok(last) => last,
+                Err(error) => {
+                    self.runtime_error(error);
+                    None
+                }
+            };
+            self.timings.evaluate = started.elapsed();
+
+            if let (true, Some(statement), Some(value)) =
+                (self.print_result, statements.last(), last)
+            {
+                self.print_last_value(statement, value);
+            }
+
+            if self.time {
+                eprintln!("{}", self.timings);
+            }
+            if self.dump_env {
+                self.dump_environment(&environment.borrow());
+            }
+            self.globals = Some(environment);
+        }
+    }
+
+    // Runs a program with the default options and gives back everything it printed, or the
+    // first error that stopped it. `exit(0)` counts as finishing normally
+    pub fn run_capture(source: &str) -> Result<String, LoxError> {
+        let mut tokens = Vec::new();
+        let mut scanner = scanner::Scanner::new();
+        for result in scanner.iter(source) {
+            tokens.push(result?);
+        }
+
+        let mut parser = parser::Parser::new(tokens);
+        parser.parse();
+        if let Some(error) = parser.errors.into_iter().next() {
+            return Err(LoxError::Parse(error));
+        }
+
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let evaluator = evaluator::Evaluator::with_output(output.clone());
+        let globals = Rc::new(RefCell::new(environment::Environment::globals()));
+        match run_statements(&evaluator, &parser.statements, &globals) {
+            Ok(_) | Err(RuntimeError::Exit(0)) => {}
+            Err(error) => return Err(LoxError::Runtime(error)),
+        }
+
+        let printed = output.borrow().clone();
+        Ok(String::from_utf8_lossy(&printed).into_owned())
+    }
+
+    // Runs more top-level code against the globals left by `run` or an earlier call, a
+    // program that fails to parse or stops with an error leaves the globals as they were
+    pub fn eval_more(&mut self, source: &str) {
+        self.exit_code = 0;
+        let statements = match self.parse_program(source) {
+            Some(statements) => statements,
+            None => return,
+        };
+
+        let environment = self
+            .globals
+            .get_or_insert_with(|| Rc::new(RefCell::new(environment::Environment::globals())))
+            .clone();
+        let snapshot = environment.borrow().map.borrow().clone();
+        let evaluator = self.evaluator();
+
+        for statement in statements.iter() {
+            if let Err(error) = evaluator.evaluate(statement, &environment, None) {
+                *environment.borrow().map.borrow_mut() = snapshot;
+                self.runtime_error(error);
+                break;
+            }
+        }
+    }
+
+    fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            print_expressions: self.print_expressions,
+            integers: self.integers,
+        }
+    }
+
+    fn parser(&self, tokens: Vec<Token>) -> parser::Parser {
+        parser::Parser::new(tokens)
+            .with_print_expressions(self.print_expressions)
+            .with_integers(self.integers)
+    }
+
+    fn evaluator(&self) -> evaluator::Evaluator {
+        let evaluator = evaluator::Evaluator::with_output(self.output.clone())
+            .with_binding(self.binding)
+            .with_strict_variables(self.strict_variables)
+            .with_strict_booleans(self.strict_booleans)
+            .with_print_expressions(self.print_expressions)
+            .with_integers(self.integers)
+            .with_number_formatter(self.number_formatter.clone())
+            .with_input(self.input.clone())
+            .with_sandbox(self.sandbox);
+        match &self.path {
+            Some(path) => evaluator.with_file(path),
+            None => evaluator,
+        }
+    }
+
+    // Phases of the last `run`, scanning and parsing stay zero when the parse cache was used
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    // Keeps parsed programs around so later runs of the same source skip the parser
+    pub fn enable_parse_cache(&mut self) {
+        self.parse_cache.get_or_insert_with(ParseCache::new);
+    }
+
+    pub fn parse_cache(&self) -> Option<&ParseCache> {
+        self.parse_cache.as_ref()
+    }
+
+    // Errors are reported here, only programs that parsed cleanly are returned and cached
+    fn parse_program(&mut self, source: &str) -> Option<Rc<Vec<Expr>>> {
+        let options = self.parse_options();
+        if let Some(statements) = self
+            .parse_cache
+            .as_mut()
+            .and_then(|cache| cache.get(source, options))
+        {
+            return Some(statements);
+        }
+
+        let started = Instant::now();
+        let mut error_code = 0;
+        let mut scanner = scanner::Scanner::new();
+        scanner.scan_tokens(source, &mut error_code);
+        self.timings.scan = started.elapsed();
+
+        // Parse errors are still reported after a scan error, but nothing runs
+        let statements = self.parse_tokens(scanner.tokens);
+        if error_code != 0 {
+            self.exit_code = 65;
+            return None;
+        }
+        let statements = Rc::new(statements?);
+        if let Some(cache) = self.parse_cache.as_mut() {
+            cache.insert(source, options, statements.clone());
+        }
+        Some(statements)
+    }
+
+    // Parse errors and warnings are written to stderr, None means the program must not run
+    fn parse_tokens(&mut self, tokens: Vec<Token>) -> Option<Vec<Expr>> {
+        let started = Instant::now();
+        let mut parser = self.parser(tokens);
+        parser.parse();
+        self.timings.parse = started.elapsed();
+        if !parser.errors.is_empty() {
+            self.parse_errors(&parser.errors);
+            return None;
+        }
+        let level = self.warning_level();
+        if level == DiagnosticLevel::Error && !parser.warnings.is_empty() {
+            for warning in parser.warnings.iter() {
+                eprintln!("{}", warning.at_level(level));
+            }
+            self.exit_code = 65;
+            return None;
+        }
+        if self.show_warnings {
+            for warning in parser.warnings.iter() {
+                eprintln!("{}", warning.at_level(level));
+            }
+        }
+
+        Some(parser.statements)
+    }
+
+    fn print_last_value(&self, statement: &Expr, value: EvaluatorReturn) {
+        let nil = matches!(
+            value,
+            EvaluatorReturn::Expr(Expr::Nil | Expr::Literal(Literal::Nil))
+        );
+        if statement.is_expression_statement() && !nil {
+            runner::interpret(
+                &mut *self.output.borrow_mut(),
+                value,
+                self.number_formatter.as_ref(),
             );
+        }
+    }
 
-            let mut index = 0;
-            while index < parser.statements.len() {
-                let s = &parser.statements[index];
-                let evaluated = evaluator.evaluate(s, &environment, None);
-                match evaluated {
-                    EvaluatorReturn::Expr(e) => {
-                        runner::interpret(e);
+    // Natives and `builtins` are left out, they are the same for every program
+    fn dump_environment(&self, environment: &environment::Environment) {
+        for (name, value) in environment.entries() {
+            if let EnvironmentValue::Expr(Expr::Module {
+                read_only: true, ..
+            }) = value
+            {
+                continue;
+            }
+            if let EnvironmentValue::Expr(e) = value {
+                writeln!(
+                    self.output.borrow_mut(),
+                    "{} = {}",
+                    name,
+                    match &self.number_formatter {
+                        Some(number) => stringify_with(&EvaluatorReturn::Expr(e), number.as_ref()),
+                        None => stringify(&EvaluatorReturn::Expr(e)),
                     }
-                    _ => {}
-                }
-                index += 1;
+                )
+                .unwrap();
             }
+        }
+    }
+
+    fn warning_level(&self) -> DiagnosticLevel {
+        if self.strict {
+            DiagnosticLevel::Error
         } else {
-            println!("EOF  null"); // Placeholder, remove this line when implementing the Scanner
+            DiagnosticLevel::Warning
         }
     }
+
+    fn parse_errors(&mut self, errors: &[ParseError]) {
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        self.exit_code = 65;
+    }
+
+    // `exit` only sets the code, real errors are reported the same way as in reference Lox
+    fn runtime_error(&mut self, error: RuntimeError) {
+        match error {
+            RuntimeError::Exit(code) => self.exit_code = code,
+            error => {
+                eprintln!("{}", error);
+                self.exit_code = 70;
+            }
+        }
+    }
+}
+
+// Top-level functions are declared before anything runs so they can be called from above their
+// declaration, variables still only exist once their line has run. Gives the value of the last
+// statement
+fn run_statements(
+    evaluator: &evaluator::Evaluator,
+    statements: &[Expr],
+    environment: &Rc<RefCell<environment::Environment>>,
+) -> Result<Option<EvaluatorReturn>, RuntimeError> {
+    let functions = statements
+        .iter()
+        .filter(|statement| matches!(statement, Expr::Function { .. }));
+
+    let mut last = None;
+    for statement in functions.chain(statements.iter()) {
+        last = Some(evaluator.evaluate(statement, environment, None)?);
+    }
+    Ok(last)
 }
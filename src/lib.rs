@@ -0,0 +1,17 @@
+pub mod cache;
+pub mod diagnostics;
+pub mod environment;
+pub mod error;
+pub mod evaluator;
+pub mod format;
+pub mod formatters;
+pub mod formula;
+pub mod interpreter;
+pub mod natives;
+pub mod parser;
+pub mod repl;
+pub mod runner;
+pub mod scanner;
+pub mod visitor;
+
+pub use interpreter::{Expr, Literal, Span, Token, TokenType};
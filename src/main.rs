@@ -1,25 +1,64 @@
-use interpreter::{Expr, Literal, Token, TokenType};
-use std::env;
-use std::io::{self, Write};
-
-mod environment;
-mod evaluator;
-mod formatters;
-mod interpreter;
-mod parser;
-mod runner;
-mod scanner;
+use codecrafters_interpreter::evaluator::BindingPolicy;
+use codecrafters_interpreter::{interpreter, repl};
+use std::{env, process};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
-        writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
+    if args.get(1).map(String::as_str) == Some("repl") {
+        let mut repl = repl::Repl::new();
+        if let Some(position) = args.iter().position(|arg| arg == "--load") {
+            let Some(path) = args.get(position + 1) else {
+                eprintln!("Usage: {} repl --load <filename>", args[0]);
+                process::exit(64);
+            };
+            if let Err(error) = repl.load(path) {
+                eprintln!("{}", error);
+                process::exit(65);
+            }
+        }
+        repl.start();
+        if let Some(code) = repl.exit_code() {
+            process::exit(code);
+        }
         return;
     }
 
-    let command = &args[1];
-    let filename = &args[2];
+    // Flags may appear anywhere after the program name
+    let (flags, positional): (Vec<&String>, Vec<&String>) =
+        args.iter().skip(1).partition(|arg| arg.starts_with("--"));
+
+    if positional.len() < 2 {
+        eprintln!("Usage: {} tokenize <filename>", args[0]);
+        return;
+    }
+
+    let command = positional[0];
+    let filename = positional[1];
     let mut interpreter = interpreter::Interpreter::new(filename);
+    interpreter.dump_env = flags.iter().any(|flag| *flag == "--dump-env");
+    interpreter.show_warnings = !flags.iter().any(|flag| *flag == "--no-warnings");
+    interpreter.integers = flags.iter().any(|flag| *flag == "--integers");
+    interpreter.print_expressions = flags.iter().any(|flag| *flag == "--print-expressions");
+    interpreter.print_result = flags.iter().any(|flag| *flag == "--print-result");
+    interpreter.sandbox = flags.iter().any(|flag| *flag == "--sandbox");
+    interpreter.strict = flags.iter().any(|flag| *flag == "--strict");
+    interpreter.strict_booleans = flags.iter().any(|flag| *flag == "--strict-booleans");
+    interpreter.strict_variables = flags.iter().any(|flag| *flag == "--strict-variables");
+    interpreter.time = flags.iter().any(|flag| *flag == "--time");
+    if flags.iter().any(|flag| *flag == "--early-binding") {
+        interpreter.binding = BindingPolicy::Early;
+    }
+
+    // Editors only want the errors, so the command itself is not carried out
+    if flags.iter().any(|flag| *flag == "--diagnostics-json") {
+        interpreter.diagnostics();
+        process::exit(interpreter.exit_code);
+    }
+
+    if flags.iter().any(|flag| *flag == "--check") {
+        interpreter.check();
+        process::exit(interpreter.exit_code);
+    }
 
     match command.as_str() {
         "tokenize" => {
@@ -34,9 +73,15 @@ fn main() {
         "run" => {
             interpreter.run();
         }
+        "fmt" => {
+            interpreter.format();
+        }
         _ => {
-            writeln!(io::stderr(), "Unknown command: {}", command).unwrap();
-            return;
+            eprintln!("Unknown command: {}", command);
         }
     }
+
+    if interpreter.exit_code != 0 {
+        process::exit(interpreter.exit_code);
+    }
 }
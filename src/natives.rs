@@ -0,0 +1,849 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::environment::{Environment, EnvironmentValue};
+use crate::error::RuntimeError;
+use crate::evaluator::Evaluator;
+use crate::formatters::stringify;
+use crate::interpreter::{CallReturn, EvaluatorReturn, Global, LoxCallable};
+use crate::scanner;
+use crate::Expr;
+
+// Defines every native function in the global environment, and again in the read-only
+// `builtins` module so `builtins.clock()` still works after a script declares its own `clock`
+pub fn define_globals(environment: &Environment) {
+    define_natives(environment);
+
+    let builtins = Environment::new();
+    define_natives(&builtins);
+    environment.define(
+        "builtins",
+        EnvironmentValue::Expr(Expr::Module {
+            name: String::from("builtins"),
+            environment: Rc::new(RefCell::new(builtins)),
+            read_only: true,
+        }),
+    );
+}
+
+fn define_natives(environment: &Environment) {
+    environment.define(
+        "clock",
+        EnvironmentValue::Global(Global::Clock(Clock::new())),
+    );
+    environment.define(
+        "to_number",
+        EnvironmentValue::Global(Global::ToNumber(ToNumber {})),
+    );
+    environment.define(
+        "to_bool",
+        EnvironmentValue::Global(Global::ToBool(ToBool {})),
+    );
+    environment.define("exit", EnvironmentValue::Global(Global::Exit(Exit {})));
+    environment.define(
+        "format_number",
+        EnvironmentValue::Global(Global::FormatNumber(FormatNumber {})),
+    );
+    environment.define(
+        "assert_eq",
+        EnvironmentValue::Global(Global::AssertEq(AssertEq {})),
+    );
+    environment.define(
+        "approx_eq",
+        EnvironmentValue::Global(Global::ApproxEq(ApproxEq {})),
+    );
+    environment.define("apply", EnvironmentValue::Global(Global::Apply(Apply {})));
+    environment.define(
+        "floor_div",
+        EnvironmentValue::Global(Global::FloorDiv(FloorDiv {})),
+    );
+    environment.define("mod", EnvironmentValue::Global(Global::Mod(Mod {})));
+    environment.define("num", EnvironmentValue::Global(Global::Num(Num {})));
+    environment.define("sort", EnvironmentValue::Global(Global::Sort(Sort {})));
+    environment.define("arity", EnvironmentValue::Global(Global::Arity(Arity {})));
+    environment.define("name", EnvironmentValue::Global(Global::Name(Name {})));
+    environment.define(
+        "read_all",
+        EnvironmentValue::Global(Global::ReadAll(ReadAll {})),
+    );
+    environment.define("push", EnvironmentValue::Global(Global::Push(Push {})));
+    environment.define("pop", EnvironmentValue::Global(Global::Pop(Pop {})));
+    environment.define(
+        "insert",
+        EnvironmentValue::Global(Global::Insert(Insert {})),
+    );
+    environment.define(
+        "remove",
+        EnvironmentValue::Global(Global::Remove(Remove {})),
+    );
+    environment.define("map", EnvironmentValue::Global(Global::Map(Map {})));
+    environment.define(
+        "filter",
+        EnvironmentValue::Global(Global::Filter(Filter {})),
+    );
+    environment.define(
+        "reduce",
+        EnvironmentValue::Global(Global::Reduce(Reduce {})),
+    );
+    environment.define("sleep", EnvironmentValue::Global(Global::Sleep(Sleep {})));
+}
+
+fn native_error(message: &str) -> Result<CallReturn, RuntimeError> {
+    Err(RuntimeError::Error(String::from(message)))
+}
+
+// The array mutators change the array they are given, every variable holding it sees the change
+fn array_argument(argument: &Expr) -> Result<Rc<RefCell<Vec<Expr>>>, RuntimeError> {
+    match argument {
+        Expr::Array(elements) => Ok(elements.clone()),
+        _ => Err(RuntimeError::Error(String::from(
+            "First argument must be an array.",
+        ))),
+    }
+}
+
+// Whether `function` can be called with `count` arguments
+fn takes(function: &Expr, count: usize) -> bool {
+    (function.arity()..=function.max_arity()).contains(&count)
+}
+
+// The function or native `map`, `filter` and `reduce` call for each element, it has to take
+// `arity` arguments
+fn callback(argument: &Expr, arity: usize) -> Result<&Expr, RuntimeError> {
+    match argument {
+        function @ (Expr::Function { .. } | Expr::Native(_)) if takes(function, arity) => {
+            Ok(function)
+        }
+        Expr::Function { .. } | Expr::Native(_) => Err(RuntimeError::Error(format!(
+            "Callback must take {} argument{}.",
+            arity,
+            if arity == 1 { "" } else { "s" }
+        ))),
+        _ => Err(RuntimeError::Error(String::from(
+            "Callback must be a function.",
+        ))),
+    }
+}
+
+// Indexes from `limit` on are out of bounds
+fn index_argument(argument: &Expr, limit: usize) -> Result<usize, RuntimeError> {
+    let index = match argument {
+        Expr::Number(n) if n.fract() == 0.0 && *n >= 0.0 => *n as usize,
+        Expr::Int(n) if *n >= 0 => *n as usize,
+        _ => {
+            return Err(RuntimeError::Error(String::from(
+                "Index must be a non-negative integer.",
+            )))
+        }
+    };
+    if index >= limit {
+        return Err(RuntimeError::Error(format!(
+            "Index {} is out of bounds.",
+            index
+        )));
+    }
+    Ok(index)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Clock {}
+
+impl LoxCallable for Clock {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        _arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        Ok(CallReturn::Expr(Expr::Number(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64(),
+        )))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+// Explicit boolean to number conversion, arithmetic operators stay strict
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToNumber {}
+
+impl LoxCallable for ToNumber {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        match &arguments[0] {
+            Expr::Bool(true) => Ok(CallReturn::Expr(Expr::Number(1.0))),
+            Expr::Bool(false) => Ok(CallReturn::Expr(Expr::Number(0.0))),
+            Expr::Number(n) => Ok(CallReturn::Expr(Expr::Number(*n))),
+            Expr::Int(n) => Ok(CallReturn::Expr(Expr::Int(*n))),
+            _ => native_error("Argument must be a boolean or a number."),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+// Converts any value to a boolean using the same truthiness as conditions
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToBool {}
+
+impl LoxCallable for ToBool {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        Ok(CallReturn::Expr(Expr::Bool(arguments[0].is_truthy())))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+// Stops the script with the given status, the evaluator unwinds and the caller of the
+// interpreter decides what to do with the code
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exit {}
+
+impl LoxCallable for Exit {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        match &arguments[0] {
+            Expr::Number(n) if n.fract() == 0.0 => Err(RuntimeError::Exit(*n as i32)),
+            Expr::Int(n) => Err(RuntimeError::Exit(*n as i32)),
+            _ => native_error("Exit code must be an integer."),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+// The most decimal places `format_number` writes, more than an f64 holds already
+const MAX_DECIMALS: f64 = 100.0;
+
+// A number as a string with a fixed count of decimal places, `format_number(3.14159, 2)` is "3.14"
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormatNumber {}
+
+impl LoxCallable for FormatNumber {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        match (arguments[0].as_number(), arguments[1].as_number()) {
+            (Some(n), Some(decimals))
+                if decimals.fract() == 0.0 && (0.0..=MAX_DECIMALS).contains(&decimals) =>
+            {
+                Ok(CallReturn::Expr(Expr::String(format!(
+                    "{:.*}",
+                    decimals as usize, n
+                ))))
+            }
+            (Some(_), _) => native_error("Decimals must be an integer from 0 to 100."),
+            _ => native_error("Argument must be a number."),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// Stops the script with both values in the message when they aren't equal the way `==` sees it
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssertEq {}
+
+impl LoxCallable for AssertEq {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let (left, right) = (&arguments[0], &arguments[1]);
+        if evaluator.is_equal(left, right) {
+            return Ok(CallReturn::Expr(Expr::Nil));
+        }
+        Err(RuntimeError::Error(format!(
+            "Assertion failed: {} != {}.",
+            stringify(&EvaluatorReturn::Expr(left.clone())),
+            stringify(&EvaluatorReturn::Expr(right.clone()))
+        )))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// `approx_eq(0.1 + 0.2, 0.3, 1e-9)` is true, `==` stays exact
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApproxEq {}
+
+impl LoxCallable for ApproxEq {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let numbers = arguments.iter().map(Expr::as_number).collect::<Vec<_>>();
+        match numbers[..] {
+            [Some(a), Some(b), Some(epsilon)] if epsilon >= 0.0 => {
+                Ok(CallReturn::Expr(Expr::Bool((a - b).abs() <= epsilon)))
+            }
+            [Some(_), Some(_), _] => native_error("Epsilon must be a non-negative number."),
+            _ => native_error("Arguments must be numbers."),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+// `floor_div(7, 2)` is 3 and `floor_div(-7, 2)` is -4, the quotient rounded down rather than
+// toward zero. Two integers from `--integers` give an integer
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloorDiv {}
+
+impl LoxCallable for FloorDiv {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        floored(&arguments[0], &arguments[1]).map(|(quotient, _)| CallReturn::Expr(quotient))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// What is left over by `floor_div`, so the result has the sign of the divisor: `mod(-7, 2)` is 1
+// and `mod(7, -2)` is -1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mod {}
+
+impl LoxCallable for Mod {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        floored(&arguments[0], &arguments[1]).map(|(_, remainder)| CallReturn::Expr(remainder))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// Floored division: the quotient is rounded toward negative infinity and the remainder is
+// `a - b * quotient`, so it is zero or has the sign of the divisor `b`. There is no operator
+// for either in the language, these natives are the only way to get them
+fn floored(a: &Expr, b: &Expr) -> Result<(Expr, Expr), RuntimeError> {
+    match (a, b) {
+        (Expr::Int(_) | Expr::Number(_), Expr::Int(0)) => {
+            Err(RuntimeError::Error(String::from("Division by zero.")))
+        }
+        (Expr::Int(a), Expr::Int(b)) => {
+            let overflow = || RuntimeError::Error(String::from("Integer overflow."));
+            let mut quotient = a.checked_div(*b).ok_or_else(overflow)?;
+            let mut remainder = a % b;
+            if remainder != 0 && (remainder < 0) != (*b < 0) {
+                quotient -= 1;
+                remainder += b;
+            }
+            Ok((Expr::Int(quotient), Expr::Int(remainder)))
+        }
+        _ => match (a.as_number(), b.as_number()) {
+            (Some(_), Some(0.0)) => Err(RuntimeError::Error(String::from("Division by zero."))),
+            (Some(a), Some(b)) => {
+                let quotient = (a / b).floor();
+                Ok((Expr::Number(quotient), Expr::Number(a - b * quotient)))
+            }
+            _ => Err(RuntimeError::Error(String::from(
+                "Arguments must be numbers.",
+            ))),
+        },
+    }
+}
+
+// `apply(f, [a, b])` is `f(a, b)` for a function or native, the array has to hold as many
+// elements as `f` takes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Apply {}
+
+impl LoxCallable for Apply {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        environment: &Rc<RefCell<Environment>>,
+        fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let (function, spread) = match (&arguments[0], &arguments[1]) {
+            (function @ (Expr::Function { .. } | Expr::Native(_)), Expr::Array(elements)) => {
+                (function, elements.borrow().clone())
+            }
+            (Expr::Function { .. } | Expr::Native(_), _) => {
+                return native_error("Arguments must be an array.")
+            }
+            _ => return native_error("Can only apply functions."),
+        };
+        if !takes(function, spread.len()) {
+            let expected = if function.max_arity() == function.arity() {
+                function.arity().to_string()
+            } else {
+                format!("{} to {}", function.arity(), function.max_arity())
+            };
+            return Err(RuntimeError::Error(format!(
+                "Expected {} arguments but got {}.",
+                expected,
+                spread.len()
+            )));
+        }
+        function.call(evaluator, environment, fn_bind, spread)
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// Parses a string the way the scanner reads number literals, so `num("0x1F")` is 31. Strings
+// that aren't a number give nil, leaving the check to the script
+#[derive(Clone, Debug, PartialEq)]
+pub struct Num {}
+
+impl LoxCallable for Num {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        match &arguments[0] {
+            Expr::String(s) => Ok(CallReturn::Expr(
+                scanner::parse_number(s.trim()).map_or(Expr::Nil, Expr::Number),
+            )),
+            _ => native_error("Argument must be a string."),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+// `sort(array)` returns a sorted copy in the order of `Expr::compare`, `sort(array, comparator)`
+// asks a Lox function taking two elements, a negative number puts the first one before the second
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sort {}
+
+impl LoxCallable for Sort {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        environment: &Rc<RefCell<Environment>>,
+        fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let elements = match &arguments[0] {
+            Expr::Array(elements) => elements.borrow().clone(),
+            _ => return native_error("Argument must be an array."),
+        };
+
+        let sorted = match arguments.get(1) {
+            None => merge_sort(elements, &mut |a, b| Ok(a.compare(b)))?,
+            Some(comparator @ (Expr::Function { .. } | Expr::Native(_))) => {
+                if !takes(comparator, 2) {
+                    return native_error("Comparator must take 2 arguments.");
+                }
+                merge_sort(elements, &mut |a, b| {
+                    let arguments = vec![a.clone(), b.clone()];
+                    let CallReturn::Expr(result) =
+                        comparator.call(evaluator, environment, fn_bind, arguments)?;
+                    match result.as_number() {
+                        Some(n) => Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+                        None => Err(RuntimeError::Error(String::from(
+                            "Comparator must return a number.",
+                        ))),
+                    }
+                })?
+            }
+            Some(_) => return native_error("Comparator must be a function."),
+        };
+        Ok(CallReturn::Expr(Expr::Array(Rc::new(RefCell::new(sorted)))))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn max_arity(&self) -> usize {
+        2
+    }
+}
+
+// Stable, and unlike `slice::sort_by` fine with a comparator that errors or contradicts itself
+fn merge_sort(
+    mut elements: Vec<Expr>,
+    compare: &mut impl FnMut(&Expr, &Expr) -> Result<Ordering, RuntimeError>,
+) -> Result<Vec<Expr>, RuntimeError> {
+    if elements.len() < 2 {
+        return Ok(elements);
+    }
+    let right = elements.split_off(elements.len() / 2);
+    let left = merge_sort(elements, compare)?;
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    for element in left {
+        while let Some(next) = right.peek() {
+            if compare(next, &element)? != Ordering::Less {
+                break;
+            }
+            merged.extend(right.next());
+        }
+        merged.push(element);
+    }
+    merged.extend(right);
+    Ok(merged)
+}
+
+// How many arguments a function or native takes, for natives with optional arguments the
+// fewest it accepts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arity {}
+
+impl LoxCallable for Arity {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let arity = match &arguments[0] {
+            function @ Expr::Function { .. } => function.arity(),
+            Expr::Native(native) => native.arity(),
+            _ => return native_error("Argument must be a function."),
+        };
+        Ok(CallReturn::Expr(Expr::Number(arity as f64)))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+// The name a function was declared with, natives keep theirs after being shadowed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Name {}
+
+impl LoxCallable for Name {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let name = match &arguments[0] {
+            Expr::Function { name, .. } => name.lexeme.clone(),
+            Expr::Native(native) => native.name().to_string(),
+            _ => return native_error("Argument must be a function."),
+        };
+        Ok(CallReturn::Expr(Expr::String(name)))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+// `push(array, value)` adds the value at the end
+#[derive(Clone, Debug, PartialEq)]
+pub struct Push {}
+
+impl LoxCallable for Push {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let elements = array_argument(&arguments[0])?;
+        elements.borrow_mut().push(arguments[1].clone());
+        Ok(CallReturn::Expr(Expr::Nil))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// `pop(array)` removes the last element and gives it back, nil for an empty array
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pop {}
+
+impl LoxCallable for Pop {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let elements = array_argument(&arguments[0])?;
+        let last = elements.borrow_mut().pop();
+        Ok(CallReturn::Expr(last.unwrap_or(Expr::Nil)))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
+
+// `insert(array, index, value)` moves the elements from `index` on one place back, an index
+// equal to the length appends
+#[derive(Clone, Debug, PartialEq)]
+pub struct Insert {}
+
+impl LoxCallable for Insert {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let elements = array_argument(&arguments[0])?;
+        let len = elements.borrow().len();
+        let index = index_argument(&arguments[1], len + 1)?;
+        elements.borrow_mut().insert(index, arguments[2].clone());
+        Ok(CallReturn::Expr(Expr::Nil))
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+// `remove(array, index)` takes out the element at `index` and gives it back
+#[derive(Clone, Debug, PartialEq)]
+pub struct Remove {}
+
+impl LoxCallable for Remove {
+    fn call(
+        &self,
+        _evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let elements = array_argument(&arguments[0])?;
+        let len = elements.borrow().len();
+        let index = index_argument(&arguments[1], len)?;
+        let removed = elements.borrow_mut().remove(index);
+        Ok(CallReturn::Expr(removed))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// `read_all()` gives the rest of the input as one string, stdin unless the host set another
+// source with `Interpreter::set_input`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadAll {}
+
+impl LoxCallable for ReadAll {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        _arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        match evaluator.read_input() {
+            Ok(text) => Ok(CallReturn::Expr(Expr::String(text))),
+            Err(error) => Err(RuntimeError::Error(format!("Can't read input: {}.", error))),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+}
+
+// `map(array, f)` is a new array of `f(element)` for every element, in order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Map {}
+
+impl LoxCallable for Map {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        environment: &Rc<RefCell<Environment>>,
+        fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        // Copied first, so the callback may change the array it is going over
+        let elements = array_argument(&arguments[0])?.borrow().clone();
+        let function = callback(&arguments[1], 1)?;
+
+        let mut mapped = Vec::with_capacity(elements.len());
+        for element in elements {
+            let CallReturn::Expr(value) =
+                function.call(evaluator, environment, fn_bind, vec![element])?;
+            mapped.push(value);
+        }
+        Ok(CallReturn::Expr(Expr::Array(Rc::new(RefCell::new(mapped)))))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// `filter(array, f)` is a new array of the elements `f(element)` is truthy for
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {}
+
+impl LoxCallable for Filter {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        environment: &Rc<RefCell<Environment>>,
+        fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let elements = array_argument(&arguments[0])?.borrow().clone();
+        let function = callback(&arguments[1], 1)?;
+
+        let mut kept = vec![];
+        for element in elements {
+            let CallReturn::Expr(keep) =
+                function.call(evaluator, environment, fn_bind, vec![element.clone()])?;
+            if keep.is_truthy() {
+                kept.push(element);
+            }
+        }
+        Ok(CallReturn::Expr(Expr::Array(Rc::new(RefCell::new(kept)))))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+}
+
+// `reduce(array, f, initial)` calls `f(accumulator, element)` for every element, starting with
+// `initial`, and gives the last result. An empty array gives `initial`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reduce {}
+
+impl LoxCallable for Reduce {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        environment: &Rc<RefCell<Environment>>,
+        fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let elements = array_argument(&arguments[0])?.borrow().clone();
+        let function = callback(&arguments[1], 2)?;
+
+        let mut accumulator = arguments[2].clone();
+        for element in elements {
+            let CallReturn::Expr(value) =
+                function.call(evaluator, environment, fn_bind, vec![accumulator, element])?;
+            accumulator = value;
+        }
+        Ok(CallReturn::Expr(accumulator))
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+}
+
+// `sleep(seconds)` blocks the script for that long and gives nil, in a sandbox it returns at once
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sleep {}
+
+impl LoxCallable for Sleep {
+    fn call(
+        &self,
+        evaluator: &Evaluator,
+        _environment: &Rc<RefCell<Environment>>,
+        _fn_bind: Option<&Expr>,
+        arguments: Vec<Expr>,
+    ) -> Result<CallReturn, RuntimeError> {
+        let duration = match arguments[0].as_number().map(Duration::try_from_secs_f64) {
+            Some(Ok(duration)) => duration,
+            Some(Err(_))
+                if arguments[0]
+                    .as_number()
+                    .is_some_and(|seconds| seconds > 0.0) =>
+            {
+                return native_error("Can't sleep that long.")
+            }
+            _ => return native_error("Argument must be a non-negative number."),
+        };
+        if !evaluator.sandboxed() {
+            thread::sleep(duration);
+        }
+        Ok(CallReturn::Expr(Expr::Nil))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+}
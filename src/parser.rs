@@ -1,11 +1,28 @@
-use std::process::exit;
+use crate::diagnostics::Warning;
+use crate::error::ParseError;
+use crate::interpreter::{Pattern, RESERVED_KEYWORDS};
+use crate::visitor::{walk_expr, Visitor};
+use crate::{Expr, Literal, Token, TokenType};
 
-use crate::{environment::Environment, Expr, Literal, Token, TokenType};
+// Names a `match` arm can use to test the type of its subject
+pub const TYPE_NAMES: [&str; 7] = [
+    "number", "string", "bool", "function", "module", "array", "object",
+];
 
 pub struct Parser {
     pub tokens: Vec<Token>,
     pub statements: Vec<Expr>,
+    // Suspicious but valid code, reported by the caller and never stopping the parse
+    pub warnings: Vec<Warning>,
+    // Every declaration that failed to parse, the parser skips to the next statement after each
+    pub errors: Vec<ParseError>,
     current: usize,
+    print_expressions: bool,
+    integers: bool,
+    // Locals declared in each enclosing block and whether anything has read them yet
+    scopes: Vec<Vec<(Token, bool)>>,
+    // How many loops enclose the statement being parsed, a function body starts again at 0
+    loop_depth: usize,
 }
 
 impl Parser {
@@ -14,85 +31,207 @@ impl Parser {
             tokens,
             current: 0,
             statements: vec![],
+            warnings: vec![],
+            errors: vec![],
+            print_expressions: false,
+            integers: false,
+            scopes: vec![],
+            loop_depth: 0,
         }
     }
 
-    fn invalid_error(&self, message: String) -> Expr {
-        // println!("{}", message);
-        exit(65)
+    // Lets `print` start an expression as well as a statement, `var y = print 1;` prints 1 and
+    // the evaluator gives y the printed value
+    pub fn with_print_expressions(mut self, print_expressions: bool) -> Self {
+        self.print_expressions = print_expressions;
+        self
     }
 
-    fn and(&mut self) -> Expr {
+    // `42` and `0xFF` become Expr::Int, a literal with a fraction or an exponent stays a float
+    pub fn with_integers(mut self, integers: bool) -> Self {
+        self.integers = integers;
+        self
+    }
+
+    fn invalid_error(&self, message: &str) -> ParseError {
+        self.error_at(self.peek(), message)
+    }
+
+    fn error_at(&self, token: &Token, message: &str) -> ParseError {
+        let location = if token.token_type == TokenType::EOF {
+            String::from(" at end")
+        } else {
+            format!(" at '{}'", token.lexeme)
+        };
+
+        ParseError {
+            line: token.line,
+            location,
+            message: message.to_string(),
+            column: token.column,
+            span: token.span,
+        }
+    }
+
+    // The scanner gives every NUMBER and STRING token its value, one made by hand without it is
+    // an error instead of becoming a value the evaluator has no meaning for
+    fn token_literal(&self, token: &Token) -> Result<Literal, ParseError> {
+        token
+            .literal
+            .clone()
+            .ok_or_else(|| self.error_at(token, "Literal token has no value."))
+    }
+
+    fn warn_at(&mut self, token: &Token, message: String) {
+        self.warnings.push(Warning {
+            line: token.line,
+            column: token.column,
+            span: token.span,
+            message,
+        });
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
         // Variables will be enums, having them mutable, reduces the number of heap allocations
 
-        let mut expr = self.equality();
+        let mut expr = self.equality()?;
         let mut operator: Token;
         let mut right: Expr;
 
         while self.match_operators(vec![TokenType::AND]) {
             operator = self.tokens.get(self.current - 1).unwrap().clone();
-            right = self.and();
+            right = self.equality()?;
             expr = Expr::Logical(Box::new(expr), Box::new(right), operator.clone().token_type);
         }
-        expr
+        Ok(expr)
     }
 
-    fn or(&mut self) -> Expr {
+    fn or(&mut self) -> Result<Expr, ParseError> {
         // Variables will be enums, having them mutable, reduces the number of heap allocations
 
-        let mut expr = self.and();
+        let mut expr = self.and()?;
         let mut operator: Token;
         let mut right: Expr;
 
         while self.match_operators(vec![TokenType::OR]) {
             operator = self.tokens.get(self.current - 1).unwrap().clone();
-            right = self.and();
+            right = self.and()?;
             expr = Expr::Logical(Box::new(expr), Box::new(right), operator.clone().token_type);
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn assignment(&mut self) -> Expr {
-        let expr = self.or();
+    fn assignment(&mut self) -> Result<Expr, ParseError> {
+        if self.print_expressions && self.match_operators(vec![TokenType::PRINT]) {
+            return Ok(Expr::Print(Box::new(self.assignment()?)));
+        }
+
+        let expr = self.ternary()?;
 
         if self.match_operators(vec![TokenType::EQUAL]) {
-            //  In case of error   let equals = self.tokens.get(self.current - 1).unwrap().clone();
-            let value = self.assignment();
-
-            match &expr {
-                Expr::Var(t) => {
-                    return Expr::Assign {
-                        name: String::from(t.lexeme.clone()),
-                        value: Box::new(value),
-                    };
-                }
-                _ => {
-                    // println!("err");
-                    // Error
-                    self.invalid_error(String::from("Invalid assignment target"));
-                }
-            }
+            let equals = self.tokens.get(self.current - 1).unwrap().clone();
+            let value = self.assignment()?;
+            return self.assignment_target(expr, &equals, value);
+        }
+
+        Ok(expr)
+    }
+
+    // `a ?? b` gives `b` only when `a` is nil, it binds looser than `or` and tighter than `? :`
+    fn coalesce(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.or()?;
+
+        while self.match_operators(vec![TokenType::QUESTION_QUESTION]) {
+            let right = self.or()?;
+            expr = Expr::Logical(
+                Box::new(expr),
+                Box::new(right),
+                TokenType::QUESTION_QUESTION,
+            );
+        }
+
+        Ok(expr)
+    }
+
+    // Binds looser than `??` and tighter than `=`, `a ? b : c ? d : e` groups to the right
+    fn ternary(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.coalesce()?;
+
+        if self.match_operators(vec![TokenType::QUESTION]) {
+            let then_branch = self.expression()?;
+            self.consume(
+                TokenType::COLON,
+                "Expect ':' after then branch of conditional expression.",
+            )?;
+            let else_branch = self.ternary()?;
+            return Ok(Expr::Ternary {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            });
         }
 
-        expr
+        Ok(condition)
+    }
+
+    // The left side of `=` decides what is assigned: a variable, a property or an element
+    fn assignment_target(
+        &self,
+        target: Expr,
+        equals: &Token,
+        value: Expr,
+    ) -> Result<Expr, ParseError> {
+        let value = Box::new(value);
+        match target {
+            Expr::VarRef(name) => Ok(Expr::Assign { name, value }),
+            Expr::Get {
+                object,
+                name,
+                optional: false,
+            } => Ok(Expr::Set {
+                object,
+                name,
+                value,
+            }),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => Ok(Expr::IndexSet {
+                object,
+                bracket,
+                index,
+                value,
+            }),
+            _ => Err(self.error_at(equals, "Invalid assignment target.")),
+        }
     }
 
-    pub fn expression(&mut self) -> Expr {
+    pub fn expression(&mut self) -> Result<Expr, ParseError> {
         self.assignment()
     }
 
+    // Exactly one expression with nothing after it, not even a `;`
+    pub fn single_expression(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression()?;
+        if !self.is_end() {
+            return Err(self.invalid_error("Expect end of expression."));
+        }
+        Ok(expr)
+    }
+
     // !=, ==
-    fn equality(&mut self) -> Expr {
+    fn equality(&mut self) -> Result<Expr, ParseError> {
         // Variables will be enums, having them mutable, reduces the number of heap allocations
 
-        let mut expr = self.comparison();
+        let mut expr = self.comparison()?;
         let mut operator: Token;
         let mut right: Expr;
 
         while self.match_operators(vec![TokenType::BANG_EQUAL, TokenType::EQUAL_EQUAL]) {
             operator = self.tokens.get(self.current - 1).unwrap().clone();
-            right = self.comparison();
+            right = self.comparison()?;
             expr = Expr::Binary {
                 operator,
                 left: Box::new(expr),
@@ -100,14 +239,14 @@ impl Parser {
             };
         }
 
-        expr
+        Ok(expr)
     }
 
     // >, >=, <, <=
-    fn comparison(&mut self) -> Expr {
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
         // Variables will be enums, having them mutable, reduces the number of heap allocations
 
-        let mut expr = self.term();
+        let mut expr = self.term()?;
         let mut operator: Token;
         let mut right: Expr;
 
@@ -118,7 +257,7 @@ impl Parser {
             TokenType::LESS_EQUAL,
         ]) {
             operator = self.tokens.get(self.current - 1).unwrap().clone();
-            right = self.term();
+            right = self.term()?;
             expr = Expr::Binary {
                 operator,
                 left: Box::new(expr),
@@ -126,20 +265,20 @@ impl Parser {
             };
         }
 
-        expr
+        Ok(expr)
     }
 
     // +, -
-    fn term(&mut self) -> Expr {
+    fn term(&mut self) -> Result<Expr, ParseError> {
         // Variables will be enums, having them mutable, reduces the number of heap allocations
 
-        let mut expr = self.factor();
+        let mut expr = self.factor()?;
         let mut operator: Token;
         let mut right: Expr;
 
         while self.match_operators(vec![TokenType::MINUS, TokenType::PLUS]) {
             operator = self.tokens.get(self.current - 1).unwrap().clone();
-            right = self.factor();
+            right = self.factor()?;
             expr = Expr::Binary {
                 operator,
                 left: Box::new(expr),
@@ -147,20 +286,20 @@ impl Parser {
             };
         }
 
-        expr
+        Ok(expr)
     }
 
     // /, *
-    fn factor(&mut self) -> Expr {
+    fn factor(&mut self) -> Result<Expr, ParseError> {
         // Variables will be enums, having them mutable, reduces the number of heap allocations
 
-        let mut expr = self.unary();
+        let mut expr = self.unary()?;
         let mut right: Expr;
         let mut operator: Token;
 
         while self.match_operators(vec![TokenType::SLASH, TokenType::STAR]) {
             operator = self.tokens.get(self.current - 1).unwrap().clone();
-            right = self.unary();
+            right = self.unary()?;
             expr = Expr::Binary {
                 operator,
                 left: Box::new(expr),
@@ -168,66 +307,153 @@ impl Parser {
             };
         }
 
-        expr
+        Ok(expr)
     }
 
     // !, -
-    fn unary(&mut self) -> Expr {
+    fn unary(&mut self) -> Result<Expr, ParseError> {
         if self.match_operators(vec![TokenType::BANG, TokenType::MINUS]) {
             let operator = self.tokens.get(self.current - 1).unwrap().clone();
-            let right = self.unary();
-            Expr::Unary {
+            let right = self.unary()?;
+            Ok(Expr::Unary {
                 operator: operator.clone(),
                 right: Box::new(right),
-            }
+            })
         } else {
             self.call()
         }
     }
 
-    fn call(&mut self) -> Expr {
+    fn call(&mut self) -> Result<Expr, ParseError> {
         // Variable will be enum, having it mutable, reduces the number of heap allocations
 
-        let mut expr = self.primary();
+        let mut expr = self.primary()?;
 
         loop {
             if self.match_operators(vec![TokenType::LEFT_PAREN]) {
-                expr = self.finish_call(expr);
+                expr = self.finish_call(expr)?;
+            } else if self.match_operators(vec![TokenType::DOT, TokenType::QUESTION_DOT]) {
+                let optional = self.tokens.get(self.current - 1).unwrap().token_type
+                    == TokenType::QUESTION_DOT;
+                let name = self
+                    .consume(TokenType::IDENTIFIER, "Expect property name after '.'.")?
+                    .clone();
+                expr = Expr::Get {
+                    object: Box::new(expr),
+                    name,
+                    optional,
+                };
+            } else if self.match_operators(vec![TokenType::LEFT_BRACKET]) {
+                let index = self.expression()?;
+                let bracket = self
+                    .consume(TokenType::RIGHT_BRACKET, "Expect ']' after index.")?
+                    .clone();
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                };
             } else {
                 break;
             }
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn primary(&mut self) -> Expr {
+    fn primary(&mut self) -> Result<Expr, ParseError> {
         if self.match_operators(vec![TokenType::FALSE]) {
-            return Expr::Literal(Literal::Bool(false));
+            return Ok(Expr::Literal(Literal::Bool(false)));
         } else if self.match_operators(vec![TokenType::TRUE]) {
-            return Expr::Literal(Literal::Bool(true));
+            return Ok(Expr::Literal(Literal::Bool(true)));
         } else if self.match_operators(vec![TokenType::NIL]) {
-            return Expr::Literal(Literal::Nil);
+            return Ok(Expr::Literal(Literal::Nil));
         }
 
         if self.match_operators(vec![TokenType::NUMBER, TokenType::STRING]) {
             let operator = self.tokens.get(self.current - 1).unwrap().clone();
-            return Expr::Literal(operator.clone().literal.unwrap());
+            if self.integers && operator.token_type == TokenType::NUMBER {
+                if let Some(integer) = self.integer(&operator)? {
+                    return Ok(integer);
+                }
+            }
+            return Ok(Expr::Literal(self.token_literal(&operator)?));
         }
 
         if self.match_operators(vec![TokenType::IDENTIFIER]) {
-            return Expr::Var(self.tokens.get(self.current - 1).unwrap().clone());
+            let name = self.tokens.get(self.current - 1).unwrap().clone();
+            self.mark_used(&name.lexeme);
+            return Ok(Expr::VarRef(name));
         }
 
         if self.match_operators(vec![TokenType::LEFT_PAREN]) {
-            let expr = self.expression();
-            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.");
-            return Expr::Grouping(vec![expr]);
+            let expr = self.expression()?;
+            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.")?;
+            return Ok(Expr::Grouping(Box::new(expr)));
+        }
+
+        if self.match_operators(vec![TokenType::LEFT_BRACKET]) {
+            let mut elements = vec![];
+            if !self.check(TokenType::RIGHT_BRACKET) {
+                elements.push(self.expression()?);
+                while self.match_operators(vec![TokenType::COMMA]) {
+                    elements.push(self.expression()?);
+                }
+            }
+            self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after elements.")?;
+            return Ok(Expr::List(elements));
+        }
+
+        // A `{` starting a statement is a block, everywhere else it is an object
+        if self.match_operators(vec![TokenType::LEFT_BRACE]) {
+            return self.object_literal();
+        }
+
+        Err(self.invalid_error("Expect expression."))
+    }
+
+    fn integer(&self, number: &Token) -> Result<Option<Expr>, ParseError> {
+        let lexeme = number.lexeme.replace('_', "");
+        let (digits, radix) = match lexeme.get(..2) {
+            Some("0x" | "0X") => (&lexeme[2..], 16),
+            Some("0b" | "0B") => (&lexeme[2..], 2),
+            Some("0o" | "0O") => (&lexeme[2..], 8),
+            _ if lexeme.contains(['.', 'e', 'E']) => return Ok(None),
+            _ => (&lexeme[..], 10),
+        };
+        match i64::from_str_radix(digits, radix) {
+            Ok(integer) => Ok(Some(Expr::Int(integer))),
+            Err(_) => Err(self.error_at(number, "Integer literal is too large.")),
         }
+    }
+
+    fn object_literal(&mut self) -> Result<Expr, ParseError> {
+        let mut fields: Vec<(Token, Expr)> = vec![];
+        if !self.check(TokenType::RIGHT_BRACE) {
+            loop {
+                let name = self
+                    .consume(TokenType::IDENTIFIER, "Expect field name.")?
+                    .clone();
+                if fields.iter().any(|(field, _)| field.lexeme == name.lexeme) {
+                    return Err(self.error_at(
+                        &name,
+                        &format!("Duplicate field '{}' in object.", name.lexeme),
+                    ));
+                }
+                self.consume(TokenType::COLON, "Expect ':' after field name.")?;
+                fields.push((name, self.expression()?));
 
-        self.invalid_error(String::from("Expect expression."))
+                if !self.match_operators(vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after fields.")?;
+        Ok(Expr::ObjectLiteral(fields))
     }
 
+    // Skips past the token that failed and on to the start of the next statement, which is
+    // right after a `;` or at a keyword that begins a statement
     fn synchronize(&mut self) {
         self.advance();
 
@@ -236,12 +462,15 @@ impl Parser {
                 return;
             }
 
-            match self.tokens.get(self.current - 1).unwrap().token_type {
+            match self.peek().token_type {
                 TokenType::CLASS
                 | TokenType::FUN
                 | TokenType::VAR
                 | TokenType::FOR
+                | TokenType::DO
                 | TokenType::IF
+                | TokenType::IMPORT
+                | TokenType::MATCH
                 | TokenType::WHILE
                 | TokenType::PRINT
                 | TokenType::RETURN => {
@@ -254,12 +483,23 @@ impl Parser {
         }
     }
 
-    fn consume(&mut self, token_type: TokenType, _message: &str) -> &Token {
+    // An identifier being declared, a keyword in its place gets named in the error
+    fn name(&mut self, what: &str) -> Result<&Token, ParseError> {
+        let found = self.peek();
+        if RESERVED_KEYWORDS.contains_key(found.lexeme.as_str()) {
+            return Err(self.error_at(
+                found,
+                &format!("Expect {}; '{}' is a reserved keyword.", what, found.lexeme),
+            ));
+        }
+        self.consume(TokenType::IDENTIFIER, &format!("Expect {}.", what))
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<&Token, ParseError> {
         if self.check(token_type) {
-            return self.advance();
+            return Ok(self.advance());
         }
-        self.invalid_error(String::from("Expect '") + &token_type.to_string() + "'.");
-        self.tokens.get(self.current - 1).unwrap()
+        Err(self.invalid_error(message))
     }
 
     fn match_operators(&mut self, types: Vec<TokenType>) -> bool {
@@ -281,11 +521,7 @@ impl Parser {
     }
 
     fn is_end(&self) -> bool {
-        if self.peek().token_type == TokenType::EOF {
-            true
-        } else {
-            false
-        }
+        self.peek().token_type == TokenType::EOF
     }
 
     fn peek(&self) -> &Token {
@@ -300,100 +536,165 @@ impl Parser {
     }
 
     pub fn parse(&mut self) {
-        // Variable will be enum, having it mutable, reduces the number of heap allocations
-
-        let mut declaration;
         while !self.is_end() {
-            declaration = self.declaration();
-            self.statements.push(declaration);
+            match self.declaration() {
+                Ok(declaration) => self.statements.push(declaration),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.scopes.clear();
+                    self.loop_depth = 0;
+                    self.synchronize();
+                }
+            }
         }
     }
 
-    fn declaration(&mut self) -> Expr {
+    fn declaration(&mut self) -> Result<Expr, ParseError> {
         if self.match_operators(vec![TokenType::FUN]) {
             return self.function(String::from("function"));
         }
         if self.match_operators(vec![TokenType::VAR]) {
-            match self.var_declaration() {
-                Some(expr) => expr,
-                None => {
-                    self.synchronize();
-                    Expr::Nil
-                }
-            }
+            self.var_declaration()
+        } else if self.at_global_declaration() {
+            self.advance();
+            self.global_declaration()
         } else {
             self.statement()
         }
     }
 
-    fn function(&mut self, kind: String) -> Expr {
-        let name = self
-            .consume(TokenType::IDENTIFIER, &format!("Expect {} name.", kind))
-            .clone();
+    fn function(&mut self, kind: String) -> Result<Expr, ParseError> {
+        let name = self.name(&format!("{} name", kind))?.clone();
 
         self.consume(
             TokenType::LEFT_PAREN,
             &format!("Expect '(' after {} name.", kind),
-        );
+        )?;
         let mut parameters: Vec<Token> = vec![];
 
         if !self.check(TokenType::RIGHT_PAREN) {
-            parameters.push(
-                self.consume(TokenType::IDENTIFIER, "Expect parameter name.")
-                    .clone(),
-            );
+            parameters.push(self.name("parameter name")?.clone());
             while self.match_operators(vec![TokenType::COMMA]) {
                 if parameters.len() >= 250 {
-                    self.invalid_error(String::from("Cannot have more than 250 parameters."));
+                    return Err(self.invalid_error("Cannot have more than 250 parameters."));
                 }
 
-                parameters.push(
-                    self.consume(TokenType::IDENTIFIER, "Expect parameter name.")
-                        .clone(),
-                );
+                parameters.push(self.name("parameter name")?.clone());
             }
         }
 
-        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.");
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.")?;
 
         self.consume(
             TokenType::LEFT_BRACE,
             &format!("Expect '{{' before {} body.", kind),
-        );
+        )?;
 
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
         let body = self.block();
+        self.loop_depth = enclosing_loops;
+        let body = body?;
 
-        Expr::Function {
+        Ok(Expr::Function {
             name,
             params: parameters,
             body,
             environment: None,
-        }
+        })
     }
 
-    fn var_declaration(&mut self) -> Option<Expr> {
-        let variable = self.consume(TokenType::IDENTIFIER, "Expect variable name.");
-        let variable_name: String;
+    fn var_declaration(&mut self) -> Result<Expr, ParseError> {
+        let name = self.name("variable name")?.clone();
+        if self.check(TokenType::COMMA) {
+            return self.destructure(name);
+        }
+        let variable_name = name.lexeme.clone();
 
+        // A written `nil` parses to a literal, so a bare Expr::Nil means there was no initializer
         let mut initializer = Expr::Nil;
-        variable_name = String::from(variable.lexeme.clone());
 
         if self.match_operators(vec![TokenType::EQUAL]) {
-            initializer = self.expression();
+            initializer = self.expression()?;
         }
 
         self.consume(
             TokenType::SEMICOLON,
             "Expect ';' after variable declaration.",
-        );
+        )?;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name, false));
+        }
 
-        Some(Expr::Variable {
+        Ok(Expr::VarDecl {
             name: variable_name,
             value: Box::new(initializer),
         })
     }
 
-    fn statement(&mut self) -> Expr {
+    // `global` isn't a keyword so scripts can keep using it as a name, it only starts a
+    // declaration when another name follows it
+    fn at_global_declaration(&self) -> bool {
+        self.check(TokenType::IDENTIFIER)
+            && self.peek().lexeme == "global"
+            && self
+                .tokens
+                .get(self.current + 1)
+                .is_some_and(|next| next.token_type == TokenType::IDENTIFIER)
+    }
+
+    fn global_declaration(&mut self) -> Result<Expr, ParseError> {
+        let name = self.name("variable name")?.lexeme.clone();
+        let mut value = Expr::Nil;
+        if self.match_operators(vec![TokenType::EQUAL]) {
+            value = self.expression()?;
+        }
+        self.consume(TokenType::SEMICOLON, "Expect ';' after global declaration.")?;
+
+        Ok(Expr::GlobalDecl {
+            name,
+            value: Box::new(value),
+        })
+    }
+
+    // `var x, y = f();`, the value has to be there since it is what gets split up
+    fn destructure(&mut self, first: Token) -> Result<Expr, ParseError> {
+        let mut names = vec![first];
+        while self.match_operators(vec![TokenType::COMMA]) {
+            names.push(self.name("variable name")?.clone());
+        }
+        self.consume(
+            TokenType::EQUAL,
+            "Expect '=' after variable names, a list of names needs a value.",
+        )?;
+        let value = self.expression()?;
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.extend(names.iter().map(|name| (name.clone(), false)));
+        }
+        Ok(Expr::Destructure {
+            names,
+            value: Box::new(value),
+        })
+    }
+
+    fn statement(&mut self) -> Result<Expr, ParseError> {
+        // A stray `;` is an empty statement, as in C
+        if self.match_operators(vec![TokenType::SEMICOLON]) {
+            return Ok(Expr::Nil);
+        }
+
+        if self.match_operators(vec![TokenType::BREAK]) {
+            return self.break_statement();
+        }
+
+        if self.match_operators(vec![TokenType::DO]) {
+            return self.do_while_statement();
+        }
+
         if self.match_operators(vec![TokenType::FOR]) {
             return self.for_statement();
         }
@@ -402,8 +703,16 @@ impl Parser {
             return self.if_statement();
         }
 
+        if self.match_operators(vec![TokenType::IMPORT]) {
+            return self.import_statement();
+        }
+
+        if self.match_operators(vec![TokenType::MATCH]) {
+            return self.match_statement();
+        }
+
         if self.match_operators(vec![TokenType::PRINT]) {
-            return Expr::Print(Box::new(self.print_statement()));
+            return Ok(Expr::Print(Box::new(self.print_statement()?)));
         }
 
         if self.match_operators(vec![TokenType::RETURN]) {
@@ -415,144 +724,377 @@ impl Parser {
         }
 
         if self.match_operators(vec![TokenType::LEFT_BRACE]) {
-            return Expr::Block(self.block());
+            return Ok(Expr::Block(self.block()?));
         }
 
         self.expression_statement()
     }
 
-    fn while_statement(&mut self) -> Expr {
-        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.");
-        let condition = self.expression();
-        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after 'if'.");
+    // `if (x = 5)` is most likely a typo for `==`, wrapping it in another pair of parens
+    // marks it as intended
+    fn condition(&mut self) -> Result<Expr, ParseError> {
+        let start = self.peek().clone();
+        let condition = self.expression()?;
+
+        if let Expr::Assign { .. } = condition {
+            self.warn_at(
+                &start,
+                String::from("Assignment used as a condition, did you mean '=='?"),
+            );
+        }
+
+        Ok(condition)
+    }
+
+    fn while_statement(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.tokens.get(self.current - 1).unwrap().clone();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.")?;
+        let condition = self.condition()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after 'if'.")?;
+
+        let body = self.loop_body()?;
+        self.warn_if_endless(&keyword, &condition, &body);
+
+        Ok(Expr::While(Box::new(condition), Box::new(body)))
+    }
+
+    fn do_while_statement(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.tokens.get(self.current - 1).unwrap().clone();
+        let body = self.loop_body()?;
+
+        self.consume(TokenType::WHILE, "Expect 'while' after 'do' body.")?;
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'while'.")?;
+        let condition = self.condition()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.")?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after do-while loop.")?;
+        self.warn_if_endless(&keyword, &condition, &body);
 
+        Ok(Expr::DoWhile(Box::new(body), Box::new(condition)))
+    }
+
+    fn loop_body(&mut self) -> Result<Expr, ParseError> {
+        self.loop_depth += 1;
         let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    // A loop whose condition is literally `true` only stops through its body, without a
+    // `break`, `return` or `exit` in it the program hangs
+    fn warn_if_endless(&mut self, keyword: &Token, condition: &Expr, body: &Expr) {
+        if !matches!(condition, Expr::Literal(Literal::Bool(true))) {
+            return;
+        }
+
+        let mut exits = LoopExits::default();
+        exits.visit_expr(body);
+        if !exits.found {
+            self.warn_at(
+                keyword,
+                String::from("Loop never ends, it has no 'break', 'return' or 'exit'."),
+            );
+        }
+    }
+
+    fn break_statement(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.tokens.get(self.current - 1).unwrap().clone();
+        if self.loop_depth == 0 {
+            return Err(self.error_at(&keyword, "Can't use 'break' outside of a loop."));
+        }
+        self.consume(TokenType::SEMICOLON, "Expect ';' after 'break'.")?;
 
-        Expr::While(Box::new(condition), Box::new(body))
+        Ok(Expr::Break(keyword))
     }
 
-    fn for_statement(&mut self) -> Expr {
-        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.");
+    fn for_statement(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.tokens.get(self.current - 1).unwrap().clone();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.")?;
         let initializer: Option<Expr>;
 
         if self.match_operators(vec![TokenType::SEMICOLON]) {
             initializer = None;
         } else if self.match_operators(vec![TokenType::VAR]) {
-            initializer = self.var_declaration();
+            initializer = Some(self.var_declaration()?);
         } else {
-            initializer = Some(self.expression_statement());
+            initializer = Some(self.expression_statement()?);
         }
 
         let mut condition: Option<Expr> = None;
 
         if !self.check(TokenType::SEMICOLON) {
-            condition = Some(self.expression());
+            condition = Some(self.expression()?);
         }
 
-        self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.");
+        self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.")?;
 
         let mut increment: Option<Expr> = None;
 
         if !self.check(TokenType::RIGHT_PAREN) {
-            increment = Some(self.expression());
+            increment = Some(self.expression()?);
         }
 
-        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after the clauses.");
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after the clauses.")?;
 
-        let mut body = self.statement();
+        let mut body = self.loop_body()?;
+        let names = match &initializer {
+            Some(Expr::VarDecl { name, .. }) => vec![name.clone()],
+            Some(Expr::Destructure { names, .. }) => {
+                names.iter().map(|name| name.lexeme.clone()).collect()
+            }
+            _ => vec![],
+        };
+        if !names.is_empty() {
+            body = Expr::Iteration {
+                names,
+                body: Box::new(body),
+            };
+        }
 
-        if increment != None {
-            body = Expr::Block(vec![body, Expr::Increment(Box::new(increment.unwrap()))])
+        // The increment is just an expression statement running after the body
+        if let Some(increment) = increment {
+            body = Expr::Block(vec![body, increment])
         }
 
-        if condition == None {
+        if condition.is_none() {
             condition = Some(Expr::Literal(Literal::Bool(true)));
         }
+        self.warn_if_endless(&keyword, condition.as_ref().unwrap(), &body);
 
         body = Expr::While(Box::new(condition.unwrap()), Box::new(body));
 
-        if initializer != None {
-            body = Expr::Block(vec![initializer.unwrap(), body]);
+        if let Some(initializer) = initializer {
+            body = Expr::Block(vec![initializer, body]);
         }
 
-        body
+        Ok(body)
     }
 
-    fn if_statement(&mut self) -> Expr {
-        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.");
-        let condition = self.expression();
-        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after 'if'.");
+    fn if_statement(&mut self) -> Result<Expr, ParseError> {
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.")?;
+        let condition = self.condition()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after 'if'.")?;
 
-        let then_branch = self.statement();
+        let then_branch = self.statement()?;
         let mut else_branch: Option<Box<Expr>> = None;
 
         if self.match_operators(vec![TokenType::ELSE]) {
-            else_branch = Some(Box::new(self.statement()));
+            else_branch = Some(Box::new(self.statement()?));
         }
 
-        Expr::If {
+        Ok(Expr::If {
             condition: Box::new(condition),
             then_branch: Box::new(then_branch),
             else_branch,
-        }
+        })
     }
 
-    fn block(&mut self) -> Vec<Expr> {
+    fn block(&mut self) -> Result<Vec<Expr>, ParseError> {
         let mut statements = vec![];
+        self.scopes.push(vec![]);
 
         while !self.check(TokenType::RIGHT_BRACE) && !self.is_end() {
-            statements.push(self.declaration());
+            let after_return = matches!(statements.last(), Some(Expr::Return(..)));
+            let start = self.peek().clone();
+            statements.push(self.declaration()?);
+            if after_return {
+                self.warn_at(&start, String::from("Unreachable code after 'return'."));
+            }
         }
 
-        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.");
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.")?;
 
-        statements
+        // A leading `_` marks a local that is unused on purpose
+        for (name, used) in self.scopes.pop().unwrap_or_default() {
+            if !used && !name.lexeme.starts_with('_') {
+                self.warn_at(
+                    &name,
+                    format!("Local variable '{}' is never used.", name.lexeme),
+                );
+            }
+        }
+
+        Ok(statements)
+    }
+
+    // Reads count, the innermost local with the name is the one being read
+    fn mark_used(&mut self, name: &str) {
+        let local = self.scopes.iter_mut().rev().find_map(|scope| {
+            scope
+                .iter_mut()
+                .rev()
+                .find(|(local, _)| local.lexeme == name)
+        });
+        if let Some((_, used)) = local {
+            *used = true;
+        }
     }
 
-    fn print_statement(&mut self) -> Expr {
-        let value = self.expression();
-        self.consume(TokenType::SEMICOLON, "Expect ';' after value.");
+    fn import_statement(&mut self) -> Result<Expr, ParseError> {
+        let keyword = self.tokens.get(self.current - 1).unwrap().clone();
+        let path = match self.consume(TokenType::STRING, "Expect path after 'import'.")? {
+            Token {
+                literal: Some(Literal::String(path)),
+                ..
+            } => path.clone(),
+            _ => String::new(),
+        };
+
+        // `as` is only special right after an import path, so it stays usable as a name
+        let mut alias = None;
+        if self.check(TokenType::IDENTIFIER) && self.peek().lexeme == "as" {
+            self.advance();
+            alias = Some(self.name("module name after 'as'")?.clone());
+        }
+        self.consume(TokenType::SEMICOLON, "Expect ';' after import path.")?;
 
-        value
+        Ok(Expr::Import {
+            keyword,
+            path,
+            alias,
+        })
     }
 
-    fn expression_statement(&mut self) -> Expr {
-        let expr = self.expression();
-        self.consume(TokenType::SEMICOLON, "Expect ';' after expression.");
+    // match x { number => ...; "a" => ...; else => ...; }, arms are tried in order and
+    // `else` has to come last
+    fn match_statement(&mut self) -> Result<Expr, ParseError> {
+        let subject = self.expression()?;
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' after match subject.")?;
+
+        let mut arms = vec![];
+        let mut fallback = None;
+
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_end() {
+            if self.match_operators(vec![TokenType::ELSE]) {
+                self.consume(TokenType::ARROW, "Expect '=>' after pattern.")?;
+                fallback = Some(Box::new(self.statement()?));
+                break;
+            }
+
+            let pattern = self.pattern()?;
+            self.consume(TokenType::ARROW, "Expect '=>' after pattern.")?;
+            arms.push((pattern, self.statement()?));
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after match arms.")?;
 
-        expr
+        Ok(Expr::Match {
+            subject: Box::new(subject),
+            arms,
+            fallback,
+        })
     }
 
-    fn return_statement(&mut self) -> Expr {
+    fn pattern(&mut self) -> Result<Pattern, ParseError> {
+        if self.match_operators(vec![TokenType::NUMBER, TokenType::STRING]) {
+            let token = self.tokens.get(self.current - 1).unwrap();
+            return Ok(Pattern::Value(self.token_literal(token)?));
+        }
+        if self.match_operators(vec![TokenType::TRUE]) {
+            return Ok(Pattern::Value(Literal::Bool(true)));
+        }
+        if self.match_operators(vec![TokenType::FALSE]) {
+            return Ok(Pattern::Value(Literal::Bool(false)));
+        }
+        if self.match_operators(vec![TokenType::NIL]) {
+            return Ok(Pattern::Value(Literal::Nil));
+        }
+        if self.check(TokenType::IDENTIFIER) && TYPE_NAMES.contains(&self.peek().lexeme.as_str()) {
+            let name = self.advance().lexeme.clone();
+            return Ok(Pattern::Type(name));
+        }
+
+        Err(self.invalid_error("Expect type name or literal pattern."))
+    }
+
+    fn print_statement(&mut self) -> Result<Expr, ParseError> {
+        let value = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
+
+        Ok(value)
+    }
+
+    fn expression_statement(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
+
+        Ok(expr)
+    }
+
+    fn return_statement(&mut self) -> Result<Expr, ParseError> {
         let keyword = self.tokens.get(self.current - 1).unwrap().clone();
         let mut value = Expr::Nil;
 
         if !self.check(TokenType::SEMICOLON) {
-            value = self.expression();
+            value = self.expression()?;
+        }
+        // `return a, b;` gives back both as one array, `var x, y = f();` takes it apart
+        if self.check(TokenType::COMMA) {
+            let mut values = vec![value];
+            while self.match_operators(vec![TokenType::COMMA]) {
+                values.push(self.expression()?);
+            }
+            value = Expr::List(values);
         }
 
-        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.");
+        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
 
-        Expr::Return(keyword, Box::new(value))
+        Ok(Expr::Return(keyword, Box::new(value)))
     }
 
-    fn finish_call(&mut self, expr: Expr) -> Expr {
+    fn finish_call(&mut self, expr: Expr) -> Result<Expr, ParseError> {
         // Variable will be enum, having it mutable, reduces the number of heap allocations
 
         let mut arguments = vec![];
 
         if !self.check(TokenType::RIGHT_PAREN) {
-            arguments.push(self.expression());
+            arguments.push(self.argument()?);
             while self.match_operators(vec![TokenType::COMMA]) {
                 if arguments.len() >= 255 {
-                    self.invalid_error(String::from("Can't have more than 255 arguments."));
+                    return Err(self.invalid_error("Can't have more than 255 arguments."));
                 }
-                arguments.push(self.expression());
+                arguments.push(self.argument()?);
             }
         }
 
-        let paren = self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.");
+        let paren = self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.")?;
 
-        Expr::Call(Box::new(expr), paren.clone(), arguments)
+        Ok(Expr::Call(Box::new(expr), paren.clone(), arguments))
+    }
+
+    // A `:` left after an argument means `f(name: value)`, which would otherwise be reported as
+    // a missing `)`
+    fn argument(&mut self) -> Result<Expr, ParseError> {
+        let argument = self.expression()?;
+        if self.check(TokenType::COLON) {
+            return Err(self.invalid_error("Named arguments are not supported."));
+        }
+        Ok(argument)
+    }
+}
+
+// Finds a way out of a loop body. A `break` counts unless it belongs to an inner loop, a
+// `return` or `exit` call anywhere except inside a function declared in the body
+#[derive(Default)]
+struct LoopExits {
+    inner_loops: usize,
+    found: bool,
+}
+
+impl Visitor for LoopExits {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Break(_) if self.inner_loops == 0 => self.found = true,
+            Expr::Return(..) => self.found = true,
+            Expr::Call(callee, ..) if matches!(&**callee, Expr::VarRef(name) if name.lexeme == "exit") => {
+                self.found = true
+            }
+            Expr::Function { .. } => {}
+            Expr::While(..) | Expr::DoWhile(..) => {
+                self.inner_loops += 1;
+                walk_expr(self, expr);
+                self.inner_loops -= 1;
+            }
+            _ => walk_expr(self, expr),
+        }
     }
 }
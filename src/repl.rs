@@ -0,0 +1,221 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::{EvaluatorReturn, Expr, Literal};
+use crate::{evaluator, parser, runner, scanner};
+
+pub struct Repl {
+    environment: Rc<RefCell<Environment>>,
+    output: Rc<RefCell<dyn Write>>,
+    prompt: String,
+    result_prefix: String,
+    exit_code: Option<i32>,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            environment: Rc::new(RefCell::new(Environment::globals())),
+            output: Rc::new(RefCell::new(io::stdout())),
+            prompt: String::from("> "),
+            result_prefix: String::new(),
+            exit_code: None,
+        }
+    }
+
+    // Set once `exit(code)` ran, the session stops reading and the caller decides what to do
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    // Shown before each new input, lines continuing an unfinished one still get `... `
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.to_string();
+    }
+
+    // Written in front of the value of a bare expression, e.g. `=> ` to show `=> 4` for `2 + 2`
+    pub fn set_result_prefix(&mut self, prefix: &str) {
+        self.result_prefix = prefix.to_string();
+    }
+
+    // Prompts and printed values go here, errors still go to stderr
+    pub fn set_output(&mut self, output: Rc<RefCell<dyn Write>>) {
+        self.output = output;
+    }
+
+    // Runs a whole file in the session before the first prompt so what it declares can be used
+    // right away. Stops at the first scan, parse or runtime error and returns it
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("Could not read {}: {}", path, error))?;
+
+        let mut tokens = Vec::new();
+        for token in scanner::Scanner::new().iter(&source) {
+            tokens.push(token.map_err(|error| error.to_string())?);
+        }
+        let mut parser = parser::Parser::new(tokens);
+        parser.parse();
+        if let Some(error) = parser.errors.first() {
+            return Err(error.to_string());
+        }
+
+        let evaluator =
+            evaluator::Evaluator::with_output(self.output.clone()).with_file(Path::new(path));
+        for statement in parser.statements.iter() {
+            evaluator
+                .evaluate(statement, &self.environment, None)
+                .map_err(|error| error.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn start(&mut self) {
+        self.read(io::stdin().lock());
+    }
+
+    pub fn read(&mut self, input: impl BufRead) {
+        let mut lines = input.lines();
+        let mut source = String::new();
+
+        while self.exit_code.is_none() {
+            let prompt = if source.is_empty() {
+                self.prompt.as_str()
+            } else {
+                "... "
+            };
+            write!(self.output.borrow_mut(), "{}", prompt).unwrap();
+            self.output.borrow_mut().flush().unwrap();
+
+            let line = match lines.next() {
+                Some(Ok(line)) => line,
+                _ => break,
+            };
+
+            // An empty line submits whatever was collected, even if it is unbalanced
+            if line.trim().is_empty() {
+                if !source.is_empty() {
+                    self.execute(&source);
+                    source.clear();
+                }
+                continue;
+            }
+
+            source.push_str(&line);
+            source.push('\n');
+
+            if is_complete(&source) {
+                self.execute(&source);
+                source.clear();
+            }
+        }
+
+        if !source.is_empty() && self.exit_code.is_none() {
+            self.execute(&source);
+        }
+    }
+
+    fn execute(&mut self, source: &str) {
+        let mut error_code = 0;
+        let mut scanner = scanner::Scanner::new();
+        scanner.scan_tokens(source, &mut error_code);
+        let mut parser = parser::Parser::new(scanner.tokens);
+        parser.parse();
+        if !parser.errors.is_empty() || error_code != 0 {
+            for error in parser.errors.iter() {
+                eprintln!("{}", error);
+            }
+            return;
+        }
+        let evaluator = evaluator::Evaluator::with_output(self.output.clone());
+
+        for statement in parser.statements.iter() {
+            match evaluator.evaluate(statement, &self.environment, None) {
+                Ok(value) => self.print_result(statement, value),
+                Err(RuntimeError::Exit(code)) => {
+                    self.exit_code = Some(code);
+                    break;
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Bare expressions like `2 + 2` show their value, declarations and statements such as `print`
+    // don't, neither does an expression that gives nil
+    fn print_result(&self, statement: &Expr, value: EvaluatorReturn) {
+        let nil = matches!(
+            value,
+            EvaluatorReturn::Expr(Expr::Nil | Expr::Literal(Literal::Nil))
+        );
+        if !statement.is_expression_statement() || nil {
+            return;
+        }
+
+        let mut output = self.output.borrow_mut();
+        write!(output, "{}", self.result_prefix).unwrap();
+        runner::interpret(&mut *output, value, None);
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Input is complete once every brace, paren and bracket is closed and no string or block
+// comment is left open, brackets inside strings and comments are not counted. A `\"` inside a
+// string doesn't end it
+pub fn is_complete(source: &str) -> bool {
+    let chars = source.chars().collect::<Vec<char>>();
+    let mut depth: i32 = 0;
+    let mut index = 0;
+
+    while index < chars.len() {
+        match chars[index] {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            '"' => {
+                index += 1;
+                while index < chars.len() && chars[index] != '"' {
+                    if chars[index] == '\\' {
+                        index += 1;
+                    }
+                    index += 1;
+                }
+                if index >= chars.len() {
+                    return false;
+                }
+            }
+            '/' if chars.get(index + 1) == Some(&'/') => {
+                while index < chars.len() && chars[index] != '\n' {
+                    index += 1;
+                }
+            }
+            '/' if chars.get(index + 1) == Some(&'*') => {
+                index += 2;
+                while index < chars.len()
+                    && !(chars[index] == '*' && chars.get(index + 1) == Some(&'/'))
+                {
+                    index += 1;
+                }
+                if index >= chars.len() {
+                    return false;
+                }
+                index += 1;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+
+    depth <= 0
+}
@@ -1,25 +1,17 @@
-use crate::Expr;
+use std::io::Write;
 
-pub fn interpret(statement: Expr) {
-    match statement {
-        Expr::Print(e) => match *e {
-            Expr::Function { name, .. } => println!("<fn {}>", name.lexeme),
-            Expr::String(s) => {
-                println!("{}", s);
-            }
-            Expr::Number(n) => {
-                println!("{}", n);
-            }
-            Expr::Bool(b) => {
-                println!("{}", b);
-            }
-            Expr::Nil => {
-                println!("nil");
-            }
-            _ => {
-                print!("Invalid expression");
-            }
-        },
-        _ => {}
-    }
+use crate::formatters::{stringify, stringify_with, NumberFormatter};
+use crate::interpreter::EvaluatorReturn;
+
+// Called once per evaluated `print` statement with the value it printed
+pub fn interpret(
+    out: &mut dyn Write,
+    value: EvaluatorReturn,
+    number_formatter: Option<&NumberFormatter>,
+) {
+    let printed = match number_formatter {
+        Some(number) => stringify_with(&value, number.as_ref()),
+        None => stringify(&value),
+    };
+    writeln!(out, "{}", printed).unwrap();
 }
@@ -1,4 +1,5 @@
-use crate::interpreter::{Literal, Token, TokenType, RESERVED_KEYWORDS};
+use crate::error::{ScanError, ScanErrorKind};
+use crate::interpreter::{Literal, Span, Token, TokenType, RESERVED_KEYWORDS};
 
 pub struct Scanner {
     pub tokens: Vec<Token>,
@@ -6,7 +7,14 @@ pub struct Scanner {
     char_count: usize,
     start: usize,
     line: u32,
+    // Index of the first character on the current line, columns are counted from it
+    line_start: usize,
+    column: u32,
     char_array: Vec<char>,
+    // Comments become COMMENT tokens instead of being skipped, for tools such as the formatter
+    keep_comments: bool,
+    // Set once EOF was handed out so the iterator ends after it
+    finished: bool,
 }
 
 impl Scanner {
@@ -17,82 +25,153 @@ impl Scanner {
             char_count: 0,
             start: 0,
             line: 1,
+            line_start: 0,
+            column: 1,
             char_array: Vec::new(),
+            keep_comments: false,
+            finished: false,
         }
     }
 
-    pub fn scan_tokens(&mut self, source: &String, error_code: &mut u8) {
+    pub fn keeping_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    pub fn scan_tokens(&mut self, source: &str, error_code: &mut u8) {
+        self.load(source);
+        while let Some(result) = self.next_token() {
+            match result {
+                Ok(token) => self.tokens.push(token),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    *error_code = 65;
+                }
+            }
+        }
+    }
+
+    // Tokens are produced one at a time as the iterator is advanced, ending with EOF
+    pub fn iter(&mut self, source: &str) -> TokenIter<'_> {
+        self.load(source);
+        TokenIter { scanner: self }
+    }
+
+    fn load(&mut self, source: &str) {
         self.char_array = source.chars().collect::<Vec<char>>();
         self.char_count = self.char_array.len();
+        self.current = 0;
+        self.line = 1;
+        self.line_start = 0;
+        self.finished = false;
+    }
 
+    fn next_token(&mut self) -> Option<Result<Token, ScanError>> {
         while self.current < self.char_count {
-            let c = self.char_array.get(self.current).unwrap();
+            let c = *self.char_array.get(self.current).unwrap();
             self.start = self.current;
+            self.column = (self.start - self.line_start + 1) as u32;
             self.current += 1;
-            match c {
-                '(' => self.tokens.push(Token::new(
+            let token = match c {
+                '(' => Some(Ok(Token::new(
                     TokenType::LEFT_PAREN,
                     String::from("("),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
-                ')' => self.tokens.push(Token::new(
+                ))),
+                ')' => Some(Ok(Token::new(
                     TokenType::RIGHT_PAREN,
                     String::from(")"),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
-                '{' => self.tokens.push(Token::new(
+                ))),
+                '{' => Some(Ok(Token::new(
                     TokenType::LEFT_BRACE,
                     String::from("{"),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
-                '}' => self.tokens.push(Token::new(
+                ))),
+                '}' => Some(Ok(Token::new(
                     TokenType::RIGHT_BRACE,
                     String::from("}"),
-                    Option::from(Literal::Null),
+                    None,
+                    self.line,
+                ))),
+                '[' => Some(Ok(Token::new(
+                    TokenType::LEFT_BRACKET,
+                    String::from("["),
+                    None,
+                    self.line,
+                ))),
+                ']' => Some(Ok(Token::new(
+                    TokenType::RIGHT_BRACKET,
+                    String::from("]"),
+                    None,
                     self.line,
-                )),
-                ',' => self.tokens.push(Token::new(
+                ))),
+                ',' => Some(Ok(Token::new(
                     TokenType::COMMA,
                     String::from(","),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
-                '.' => self.tokens.push(Token::new(
+                ))),
+                '.' => Some(Ok(Token::new(
                     TokenType::DOT,
                     String::from("."),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
-                '-' => self.tokens.push(Token::new(
+                ))),
+                '-' => Some(Ok(Token::new(
                     TokenType::MINUS,
                     String::from("-"),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
-                '+' => self.tokens.push(Token::new(
+                ))),
+                '+' => Some(Ok(Token::new(
                     TokenType::PLUS,
                     String::from("+"),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
-                ';' => self.tokens.push(Token::new(
+                ))),
+                ';' => Some(Ok(Token::new(
                     TokenType::SEMICOLON,
                     String::from(";"),
-                    Option::from(Literal::Null),
+                    None,
+                    self.line,
+                ))),
+                ':' => Some(Ok(Token::new(
+                    TokenType::COLON,
+                    String::from(":"),
+                    None,
+                    self.line,
+                ))),
+                '?' if self.match_operator('.') => Some(Ok(Token::new(
+                    TokenType::QUESTION_DOT,
+                    String::from("?."),
+                    None,
+                    self.line,
+                ))),
+                '?' if self.match_operator('?') => Some(Ok(Token::new(
+                    TokenType::QUESTION_QUESTION,
+                    String::from("??"),
+                    None,
+                    self.line,
+                ))),
+                '?' => Some(Ok(Token::new(
+                    TokenType::QUESTION,
+                    String::from("?"),
+                    None,
                     self.line,
-                )),
-                '*' => self.tokens.push(Token::new(
+                ))),
+                '*' => Some(Ok(Token::new(
                     TokenType::STAR,
                     String::from("*"),
-                    Option::from(Literal::Null),
+                    None,
                     self.line,
-                )),
+                ))),
                 '!' => {
                     let is_bang = self.match_operator('=');
-                    self.tokens.push(Token::new(
+                    Some(Ok(Token::new(
                         if is_bang {
                             TokenType::BANG_EQUAL
                         } else {
@@ -103,13 +182,19 @@ impl Scanner {
                         } else {
                             String::from("!")
                         },
-                        Option::from(Literal::Null),
+                        None,
                         self.line,
-                    ))
+                    )))
                 }
+                '=' if self.match_operator('>') => Some(Ok(Token::new(
+                    TokenType::ARROW,
+                    String::from("=>"),
+                    None,
+                    self.line,
+                ))),
                 '=' => {
                     let is_equal = self.match_operator('=');
-                    self.tokens.push(Token::new(
+                    Some(Ok(Token::new(
                         if is_equal {
                             TokenType::EQUAL_EQUAL
                         } else {
@@ -120,13 +205,13 @@ impl Scanner {
                         } else {
                             String::from("=")
                         },
-                        Option::from(Literal::Null),
+                        None,
                         self.line,
-                    ))
+                    )))
                 }
                 '<' => {
                     let is_less = self.match_operator('=');
-                    self.tokens.push(Token::new(
+                    Some(Ok(Token::new(
                         if is_less {
                             TokenType::LESS_EQUAL
                         } else {
@@ -137,13 +222,13 @@ impl Scanner {
                         } else {
                             String::from("<")
                         },
-                        Option::from(Literal::Null),
+                        None,
                         self.line,
-                    ))
+                    )))
                 }
                 '>' => {
                     let is_greater = self.match_operator('=');
-                    self.tokens.push(Token::new(
+                    Some(Ok(Token::new(
                         if is_greater {
                             TokenType::GREATER_EQUAL
                         } else {
@@ -154,94 +239,122 @@ impl Scanner {
                         } else {
                             String::from(">")
                         },
-                        Option::from(Literal::Null),
+                        None,
                         self.line,
-                    ))
+                    )))
                 }
                 '/' => {
                     let matched = self.match_operator('/');
                     if matched {
-                        while self.peek() != '\n' && !self.is_end() {
+                        while !matches!(self.peek(), '\n' | '\r') && !self.is_end() {
                             self.current += 1;
                         }
+                        self.comment_token()
+                    } else if self.match_operator('*') {
+                        match self.block_comment_process() {
+                            Ok(()) => self.comment_token(),
+                            Err(error) => Some(Err(error)),
+                        }
                     } else {
-                        self.tokens.push(Token::new(
+                        Some(Ok(Token::new(
                             TokenType::SLASH,
                             String::from("/"),
-                            Option::from(Literal::Null),
+                            None,
                             self.line,
-                        ));
+                        )))
                     }
                 }
-                '"' => {
-                    match self.string_process() {
-                        Ok(string) => {
-                            *error_code = 0;
-                            self.tokens.push(Token::new(
-                                TokenType::STRING,
-                                string.clone(),
-                                Option::from(if string.len() > 1 {
-                                    // Need to cut \ for string value "
-                                    Literal::String(string[1..string.len() - 1].to_string())
-                                } else {
-                                    Literal::String(String::new())
-                                }),
-                                self.line,
-                            ));
-                        }
-                        Err(_) => {
-                            *error_code = 65;
-                        }
+                '"' => Some(self.string_token(false)),
+                ' ' | '\r' | '\t' | '\n' => {
+                    if self.ends_line(self.start) {
+                        self.line += 1;
+                        self.line_start = self.current;
                     }
+                    None
                 }
-                ' ' | '\r' | '\t' => (),
-                '\n' => self.line += 1,
                 _ => {
-                    if self.is_digit(*c) {
-                        let number = self.number_process();
-                        self.tokens.push(Token::new(
-                            TokenType::NUMBER,
-                            format!("{:.*}", number.1, number.0),
-                            Option::from(Literal::Number((number.0, number.1))),
-                            self.line,
-                        ));
-                    } else if self.is_alpha(*c) {
+                    // r"..." is a raw string, anything else starting with r is an identifier
+                    if c == 'r' && self.peek() == '"' {
+                        self.current += 1;
+                        Some(self.string_token(true))
+                    } else if self.is_digit(c) {
+                        Some(self.number_process().map(|number| {
+                            Token::new(
+                                TokenType::NUMBER,
+                                number.2,
+                                Option::from(Literal::Number((number.0, number.1))),
+                                self.line,
+                            )
+                        }))
+                    } else if self.is_alpha(c) {
                         let identifier_value = self.identifier();
-                        self.tokens.push(Token::new(
+                        Some(Ok(Token::new(
                             identifier_value.1,
                             identifier_value.0,
-                            Option::from(Literal::Null),
+                            None,
                             self.line,
-                        ))
+                        )))
                     } else {
-                        eprintln!("[line {}] Error: Unexpected character: {}", self.line, c);
-                        *error_code = 65;
+                        Some(Err(self.scan_error(
+                            ScanErrorKind::UnexpectedChar,
+                            format!("Unexpected character: {}", c),
+                        )))
                     }
                 }
+            };
+
+            if let Some(token) = token {
+                return Some(token.map(|token| self.locate(token)));
             }
         }
-        self.tokens.push(Token::new(
+
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        self.start = self.current;
+        self.column = (self.start - self.line_start + 1) as u32;
+        Some(Ok(self.locate(Token::new(
             TokenType::EOF,
             String::new(),
-            Option::from(Literal::Null),
+            None,
             self.line,
-        ));
+        ))))
     }
 
-    fn is_end(&mut self) -> bool {
-        if self.char_count <= self.current {
-            true
-        } else {
-            false
+    fn locate(&self, mut token: Token) -> Token {
+        token.column = self.column;
+        token.span = self.span();
+        token
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+        }
+    }
+
+    fn scan_error(&self, kind: ScanErrorKind, message: String) -> ScanError {
+        ScanError {
+            kind,
+            line: self.line,
+            message,
+            column: self.column,
+            span: self.span(),
         }
     }
 
+    fn is_end(&mut self) -> bool {
+        self.char_count <= self.current
+    }
+
     fn is_alpha_numeric(&mut self, c: char) -> bool {
         self.is_alpha(c) || self.is_digit(c)
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+        c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
     }
 
     fn identifier(&mut self) -> (String, TokenType) {
@@ -251,91 +364,258 @@ impl Scanner {
             peeked_value = self.peek();
         }
 
-        let type_of_token = self.char_array[self.start..self.current]
-            .iter()
-            .collect::<String>();
-
-        if RESERVED_KEYWORDS
-            .lock()
-            .unwrap()
-            .contains_key(&type_of_token.as_str())
-        {
-            (
-                type_of_token.clone(),
-                TokenType::from(
-                    RESERVED_KEYWORDS
-                        .lock()
-                        .unwrap()
-                        .get(&type_of_token.as_str())
-                        .unwrap()
-                        .clone(),
-                ),
-            )
-        } else {
-            (type_of_token.clone(), TokenType::IDENTIFIER)
-        }
+        let lexeme = self.lexeme();
+        let token_type = RESERVED_KEYWORDS
+            .get(lexeme.as_str())
+            .copied()
+            .unwrap_or(TokenType::IDENTIFIER);
+        (lexeme, token_type)
     }
 
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
-    fn number_process(&mut self) -> (f64, usize, String) {
-        let mut peeked_value: char = self.peek();
+    // The lexeme keeps the source spelling (`0xFF`, `1_000`, `1e3`), the value is the parsed
+    // f64 with underscores dropped
+    fn number_process(&mut self) -> Result<(f64, usize, String), ScanError> {
+        if self.char_array[self.start] == '0' {
+            let radix = match self.peek() {
+                'x' | 'X' => 16,
+                'b' | 'B' => 2,
+                'o' | 'O' => 8,
+                _ => 0,
+            };
+            let has_digit = self
+                .char_array
+                .get(self.current + 1)
+                .is_some_and(|c| c.is_digit(radix.max(2)));
+            if radix != 0 && has_digit {
+                self.current += 1;
+                let digits_start = self.current;
+                self.digits(radix);
 
-        while self.is_digit(peeked_value) && !self.is_end() {
-            self.current += 1;
-            peeked_value = self.peek();
+                let digits = self.char_array[digits_start..self.current]
+                    .iter()
+                    .filter(|c| **c != '_')
+                    .collect::<String>();
+                let number = u64::from_str_radix(&digits, radix).unwrap_or(u64::MAX) as f64;
+                return Ok((number, 0, self.lexeme()));
+            }
         }
 
+        self.digits(10);
+
         let mut formatting_size: usize = 0;
-        // This uses regular peek fn, but adds current + 1, it will not throw cause we handle that
-        // in peek() or more exactly is_end()
-        if peeked_value == '.' {
+        // The `.` is only part of the number when a digit follows it, `123.` and `1.foo` end the
+        // number before the dot
+        if self.peek() == '.'
+            && self
+                .char_array
+                .get(self.current + 1)
+                .is_some_and(|c| c.is_ascii_digit())
+        {
             self.current += 1;
-            peeked_value = self.peek();
-            while self.is_digit(peeked_value) && !self.is_end() {
-                formatting_size += 1;
-                self.current += 1;
-                peeked_value = self.peek();
+            formatting_size = self.digits(10);
+
+            // `1.2.3` is one bad number rather than `1.2`, `.` and `3`, a second dot only counts
+            // once the fraction has a digit so `1..2` is still `1`, `.`, `.` and `2`
+            if formatting_size > 0
+                && self.peek() == '.'
+                && self
+                    .char_array
+                    .get(self.current + 1)
+                    .is_some_and(|c| c.is_ascii_digit())
+            {
+                while self.peek() == '.' || self.peek().is_ascii_digit() || self.peek() == '_' {
+                    self.current += 1;
+                }
+                return Err(self.scan_error(
+                    ScanErrorKind::MalformedNumber,
+                    String::from("Malformed number."),
+                ));
+            }
+        }
+
+        // An exponent needs at least one digit, otherwise `e` starts an identifier
+        if matches!(self.peek(), 'e' | 'E') {
+            let mut exponent = self.current + 1;
+            if matches!(self.char_array.get(exponent), Some('+' | '-')) {
+                exponent += 1;
+            }
+            if self
+                .char_array
+                .get(exponent)
+                .is_some_and(|c| c.is_ascii_digit())
+            {
+                self.current = exponent;
+                self.digits(10);
+            }
+        }
+
+        let string = self.lexeme();
+        let number = string.replace('_', "").parse::<f64>().unwrap();
+
+        Ok((number, formatting_size, string))
+    }
+
+    // Consumes digits of the given radix, underscores are allowed between them as separators
+    fn digits(&mut self, radix: u32) -> usize {
+        let mut count = 0;
+        let mut peeked_value: char = self.peek();
+        while !self.is_end() && (peeked_value.is_digit(radix) || peeked_value == '_') {
+            if peeked_value != '_' {
+                count += 1;
             }
+            self.current += 1;
+            peeked_value = self.peek();
         }
+        count
+    }
 
-        let number = self.char_array[self.start..self.current]
+    fn lexeme(&self) -> String {
+        self.char_array[self.start..self.current]
             .iter()
             .collect::<String>()
-            .parse::<f64>()
-            .unwrap();
-        let string = self.char_array[self.start..self.current]
-            .iter()
-            .collect::<String>();
+    }
 
-        (number, formatting_size, string)
+    fn string_token(&mut self, raw: bool) -> Result<Token, ScanError> {
+        let (lexeme, value) = self.string_process(raw)?;
+        Ok(Token::new(
+            TokenType::STRING,
+            lexeme,
+            Option::from(Literal::String(value)),
+            self.line,
+        ))
     }
 
-    fn string_process(&mut self) -> Result<String, u8> {
+    // Returns the lexeme as written in source and the value with escapes resolved, raw
+    // strings keep every backslash as it is
+    fn string_process(&mut self, raw: bool) -> Result<(String, String), ScanError> {
+        let mut value = String::new();
+        // The rest of the string is still consumed after a bad escape, the first one is reported
+        let mut error = None;
         let mut peeked_value: char = self.peek();
         while peeked_value != '"' && !self.is_end() {
-            if peeked_value == '\n' {
+            if self.ends_line(self.current) {
                 self.line += 1;
+                self.line_start = self.current + 1;
             }
             self.current += 1;
+
+            if peeked_value == '\\' && !raw && !self.is_end() {
+                let escaped = self.peek();
+                self.current += 1;
+                match escaped {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    '0' => value.push('\0'),
+                    '\\' => value.push('\\'),
+                    '"' => value.push('"'),
+                    'u' => match self.unicode_escape() {
+                        Some(c) => value.push(c),
+                        None => {
+                            error = error.or(Some(self.scan_error(
+                                ScanErrorKind::InvalidEscape,
+                                String::from("Invalid unicode escape."),
+                            )));
+                        }
+                    },
+                    _ => {
+                        error = error.or(Some(self.scan_error(
+                            ScanErrorKind::InvalidEscape,
+                            format!("Invalid escape sequence: \\{}", escaped),
+                        )));
+                    }
+                }
+            } else {
+                value.push(peeked_value);
+            }
             peeked_value = self.peek();
         }
 
         if self.is_end() {
-            eprintln!("[line {}] Error: Unterminated string.", self.line);
-            return Err(65);
+            return Err(self.scan_error(
+                ScanErrorKind::UnterminatedString,
+                String::from("Unterminated string."),
+            ));
         }
 
         self.current += 1;
 
-        if (self.current - 2) == self.start {
-            Ok(['"', '"'].iter().collect::<String>())
-        } else {
-            Ok(self.char_array[self.start..self.current]
-                .iter()
-                .collect::<String>())
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        Ok((self.lexeme(), value))
+    }
+
+    // `\u{1F600}`, one to six hex digits naming a valid code point, called after the `u`
+    fn unicode_escape(&mut self) -> Option<char> {
+        if self.peek() != '{' {
+            return None;
+        }
+        self.current += 1;
+
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.current += 1;
+        }
+        let digits = self.char_array[digits_start..self.current]
+            .iter()
+            .collect::<String>();
+
+        if self.peek() != '}' {
+            return None;
+        }
+        self.current += 1;
+
+        if digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+    }
+
+    fn comment_token(&self) -> Option<Result<Token, ScanError>> {
+        if !self.keep_comments {
+            return None;
+        }
+        Some(Ok(Token::new(
+            TokenType::COMMENT,
+            self.lexeme(),
+            None,
+            self.line,
+        )))
+    }
+
+    fn block_comment_process(&mut self) -> Result<(), ScanError> {
+        while !self.is_end() {
+            if self.peek() == '*' && self.char_array.get(self.current + 1) == Some(&'/') {
+                self.current += 2;
+                return Ok(());
+            }
+            if self.ends_line(self.current) {
+                self.line += 1;
+                self.line_start = self.current + 1;
+            }
+            self.current += 1;
+        }
+
+        Err(self.scan_error(
+            ScanErrorKind::UnterminatedComment,
+            String::from("Unterminated comment."),
+        ))
+    }
+
+    // `\n`, `\r\n` and a lone `\r` each end one line, for `\r\n` the `\n` is what counts
+    fn ends_line(&self, index: usize) -> bool {
+        match self.char_array.get(index) {
+            Some('\n') => true,
+            Some('\r') => self.char_array.get(index + 1) != Some(&'\n'),
+            _ => false,
         }
     }
 
@@ -355,3 +635,74 @@ impl Scanner {
         }
     }
 }
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct TokenIter<'a> {
+    scanner: &'a mut Scanner,
+}
+
+impl Iterator for TokenIter<'_> {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scanner.next_token()
+    }
+}
+
+// Writes tokens back out as source that scans to the same tokens. Lexemes are separated by a
+// space and put back on the line they came from, comments and other spacing are lost
+pub fn to_source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    let mut line = 1;
+    for token in tokens {
+        // Strings spanning lines are reported on their last line
+        let start_line = token.line - line_breaks(&token.lexeme);
+        if start_line > line {
+            source.push_str(&"\n".repeat((start_line - line) as usize));
+        } else if !source.is_empty() && token.token_type != TokenType::EOF {
+            source.push(' ');
+        }
+        source.push_str(&token.lexeme);
+        line = token.line;
+    }
+    source
+}
+
+// How many lines a multi-line lexeme spans past its first, counted like the scanner does
+pub fn line_breaks(text: &str) -> u32 {
+    (text.matches('\n').count() + text.matches('\r').count() - text.matches("\r\n").count()) as u32
+}
+
+// Reads a whole string as one number literal, with the same spellings the scanner accepts
+// (`42`, `1_000`, `2.5e3`, `0x1F`, `0b101`, `0o17`) and an optional leading `-`. Anything
+// else, including surrounding text, gives None
+pub fn parse_number(text: &str) -> Option<f64> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, text),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut scanner = Scanner::new();
+    let mut tokens = scanner.iter(digits);
+    match (tokens.next(), tokens.next()) {
+        (
+            Some(Ok(Token {
+                literal: Some(Literal::Number((number, _))),
+                ..
+            })),
+            Some(Ok(Token {
+                token_type: TokenType::EOF,
+                ..
+            })),
+        ) => Some(sign * number),
+        _ => None,
+    }
+}
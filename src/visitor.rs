@@ -0,0 +1,124 @@
+use crate::Expr;
+
+/// Read-only pass over the AST. Every method has a default that keeps walking into
+/// child nodes, so an implementation only overrides the nodes it cares about and
+/// calls `walk_expr` to continue below them.
+pub trait Visitor {
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_statements(&mut self, statements: &[Expr]) {
+        for statement in statements {
+            self.visit_expr(statement);
+        }
+    }
+}
+
+/// Visits every direct child of `expr`, in source order.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Bool(_)
+        | Expr::Literal(_)
+        | Expr::Number(_)
+        | Expr::Int(_)
+        | Expr::Nil
+        | Expr::String(_)
+        | Expr::VarRef(_)
+        | Expr::Break(_)
+        | Expr::Import { .. }
+        | Expr::Module { .. }
+        | Expr::Native(_) => {}
+        Expr::Get { object, .. } => visitor.visit_expr(object),
+        Expr::List(elements) => visitor.visit_statements(elements),
+        Expr::Array(_) | Expr::Object(_) => {}
+        Expr::ObjectLiteral(fields) => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Index { object, index, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        }
+        Expr::Set { object, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(value);
+        }
+        Expr::IndexSet {
+            object,
+            index,
+            value,
+            ..
+        } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+            visitor.visit_expr(value);
+        }
+        Expr::Match {
+            subject,
+            arms,
+            fallback,
+        } => {
+            visitor.visit_expr(subject);
+            for (_, body) in arms {
+                visitor.visit_expr(body);
+            }
+            if let Some(fallback) = fallback {
+                visitor.visit_expr(fallback);
+            }
+        }
+        Expr::Logical(left, right, _) => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Print(value) | Expr::Return(_, value) => {
+            visitor.visit_expr(value);
+        }
+        Expr::Function { body, .. } => visitor.visit_statements(body),
+        Expr::VarDecl { value, .. }
+        | Expr::GlobalDecl { value, .. }
+        | Expr::Destructure { value, .. }
+        | Expr::Assign { value, .. } => visitor.visit_expr(value),
+        Expr::Block(statements) => visitor.visit_statements(statements),
+        Expr::Grouping(expr) => visitor.visit_expr(expr),
+        Expr::While(condition, body) => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(body);
+        }
+        Expr::Iteration { body, .. } => visitor.visit_expr(body),
+        Expr::DoWhile(body, condition) => {
+            visitor.visit_expr(body);
+            visitor.visit_expr(condition);
+        }
+        Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(then_branch);
+            visitor.visit_expr(else_branch);
+        }
+        Expr::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_expr(else_branch);
+            }
+        }
+        Expr::Unary { right, .. } => visitor.visit_expr(right),
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Call(callee, _, arguments) => {
+            visitor.visit_expr(callee);
+            visitor.visit_statements(arguments);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use codecrafters_interpreter::format::format_source;
+
+#[test]
+fn comments_survive_formatting() {
+    let source = "// Sums a list   \nfun total(items){var sum=0;   // running total\nfor(var i=0;i<3;i=i+1) sum=sum+items[i];\n\n\n  /* done */\n  return sum;}   \nif (!true) { print -1; } else {}\n";
+
+    let formatted = format_source(source).unwrap();
+    assert_eq!(
+        formatted,
+        "// Sums a list\n\
+         fun total(items) {\n  \
+           var sum = 0;\n  \
+           // running total\n  \
+           for (var i = 0; i < 3; i = i + 1) sum = sum + items[i];\n\
+         \n  \
+           /* done */\n  \
+           return sum;\n\
+         }\n\
+         if (!true) {\n  \
+           print -1;\n\
+         } else {}\n"
+    );
+    assert_eq!(format_source(&formatted).unwrap(), formatted);
+}
+
+#[test]
+fn object_literals_and_conditionals_stay_on_one_line() {
+    let source =
+        "var o={name:\"x\",inner:{list:[1,2]},none:{},n:a?1:b?{c:2}:3};\nif(o){print o.name;}\n";
+
+    assert_eq!(
+        format_source(source).unwrap(),
+        "var o = {name: \"x\", inner: {list: [1, 2]}, none: {}, n: a ? 1 : b ? {c: 2} : 3};\n\
+         if (o) {\n  \
+           print o.name;\n\
+         }\n"
+    );
+}
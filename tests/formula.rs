@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use codecrafters_interpreter::error::FormulaError;
+use codecrafters_interpreter::{formula, Expr, Span};
+
+fn variables() -> HashMap<String, Expr> {
+    HashMap::from([
+        (String::from("a"), Expr::Number(3.0)),
+        (String::from("b"), Expr::Number(4.0)),
+    ])
+}
+
+#[test]
+fn formula_uses_given_variables() {
+    assert_eq!(
+        formula::evaluate("a + b * 2", &variables()),
+        Ok(Expr::Number(11.0))
+    );
+}
+
+#[test]
+fn formula_rejects_statements_assignments_and_calls() {
+    assert_eq!(
+        formula::evaluate("print 1;", &variables())
+            .unwrap_err()
+            .to_string(),
+        "[line 1] Error at 'print': Expect expression."
+    );
+    assert_eq!(
+        formula::evaluate("a = 1", &variables()),
+        Err(FormulaError::Rejected(String::from(
+            "Can't assign to 'a' in a formula."
+        )))
+    );
+    assert_eq!(
+        formula::evaluate("clock()", &variables()),
+        Err(FormulaError::Rejected(String::from(
+            "Can't call functions in a formula."
+        )))
+    );
+}
+
+#[test]
+fn type_errors_carry_the_operator_span() {
+    let error = match formula::evaluate("a + b * \"two\"", &variables()) {
+        Err(FormulaError::Runtime(error)) => error,
+        other => panic!("expected a runtime error, got {:?}", other),
+    };
+
+    assert_eq!(error.to_string(), "Operands must be numbers.\n[line 1]");
+    assert_eq!(error.span(), Some(Span { start: 6, end: 7 }));
+}
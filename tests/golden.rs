@@ -0,0 +1,63 @@
+// Every `tests/golden/<name>.lox` is run and its output compared with `<name>.expected`, files in
+// `tests/golden/tokenize` and `tests/golden/parse` are compared with what the `tokenize` and
+// `parse` commands print for them
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use codecrafters_interpreter::interpreter::Interpreter;
+
+// Loaded from the file itself so imports resolve next to it
+fn output_of(path: &Path, command: fn(&mut Interpreter)) -> String {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::new(path.to_str().unwrap());
+    interpreter.set_output(output.clone());
+    command(&mut interpreter);
+
+    let printed = output.borrow().clone();
+    String::from_utf8(printed).unwrap()
+}
+
+fn compare_outputs(directory: &str, command: fn(&mut Interpreter)) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(directory);
+    let mut entries = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let mut failures = vec![];
+    for path in entries.iter() {
+        let expected = fs::read_to_string(path.with_extension("expected")).unwrap();
+        let actual = output_of(path, command);
+
+        if actual != expected {
+            failures.push(format!(
+                "{}\n--- expected\n{}--- actual\n{}",
+                path.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+
+    assert!(!entries.is_empty());
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn golden_outputs() {
+    compare_outputs("tests/golden", Interpreter::run);
+}
+
+#[test]
+fn golden_tokens() {
+    compare_outputs("tests/golden/tokenize", Interpreter::tokenize);
+}
+
+#[test]
+fn golden_parse_trees() {
+    compare_outputs("tests/golden/parse", Interpreter::parse);
+}
@@ -0,0 +1,725 @@
+use std::cell::RefCell;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use codecrafters_interpreter::environment::{Environment, EnvironmentValue};
+use codecrafters_interpreter::error::{LoxError, RuntimeError};
+use codecrafters_interpreter::evaluator::{BindingPolicy, Evaluator};
+use codecrafters_interpreter::interpreter::Interpreter;
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::scanner::Scanner;
+use codecrafters_interpreter::Expr;
+
+fn run_with(source: &str, configure: impl FnOnce(&mut Interpreter)) -> String {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source(source);
+    interpreter.set_output(output.clone());
+    configure(&mut interpreter);
+    interpreter.run();
+
+    let printed = output.borrow().clone();
+    String::from_utf8(printed).unwrap()
+}
+
+// The error that stopped the program, the interpreter itself only writes it to stderr
+fn runtime_error(source: &str) -> RuntimeError {
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens(source, &mut 0);
+    let mut parser = Parser::new(scanner.tokens);
+    parser.parse();
+
+    let evaluator = Evaluator::with_output(Rc::new(RefCell::new(Vec::new())));
+    let globals = Rc::new(RefCell::new(Environment::globals()));
+    parser
+        .statements
+        .iter()
+        .find_map(|statement| evaluator.evaluate(statement, &globals, None).err())
+        .expect("program ran without an error")
+}
+
+#[test]
+fn each_print_statement_writes_one_line() {
+    assert_eq!(run_with("print \"a\"; print \"b\";", |_| {}), "a\nb\n");
+    assert_eq!(
+        run_with("{ print \"a\"; } if (true) print \"b\";", |_| {}),
+        "a\nb\n"
+    );
+}
+
+#[test]
+fn deeply_nested_values_print_with_ellipsis() {
+    // Taken apart one level at a time at the end so dropping them doesn't recurse as deep
+    let printed = run_with(
+        "var a = nil;\n\
+         for (var i = 0; i < 10000; i = i + 1) a = [a];\n\
+         print a;\n\
+         var o = nil;\n\
+         for (var i = 0; i < 10000; i = i + 1) o = {inner: o};\n\
+         print o;\n\
+         while (a != nil) a = a[0];\n\
+         while (o != nil) o = o.inner;\n",
+        |_| {},
+    );
+    let lines = printed.lines().collect::<Vec<_>>();
+
+    assert_eq!(
+        lines[0],
+        format!("{}...{}", "[".repeat(201), "]".repeat(201))
+    );
+    assert!(lines[1].starts_with("{inner: {inner: "));
+    assert!(lines[1].contains("{...}"));
+    assert!(!printed.contains("nil"));
+}
+
+#[test]
+fn dump_env_lists_globals_after_run() {
+    let printed = run_with("var b = 2; var a = 1; print a + b;", |interpreter| {
+        interpreter.dump_env = true
+    });
+
+    assert_eq!(printed, "3\na = 1\nb = 2\n");
+}
+
+#[test]
+fn import_cycle_is_a_runtime_error() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/modules/cycle_a.lox");
+    let mut interpreter = Interpreter::new(path.to_str().unwrap());
+    interpreter.set_output(Rc::new(RefCell::new(Vec::new())));
+    interpreter.run();
+
+    assert_eq!(interpreter.exit_code, 70);
+}
+
+#[test]
+fn parse_cache_reuses_unchanged_source() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source("print 1 + 2;");
+    interpreter.set_output(output.clone());
+    interpreter.enable_parse_cache();
+
+    interpreter.run();
+    interpreter.run();
+    interpreter.file_contents = String::from("print 3 + 4;");
+    interpreter.run();
+
+    let cache = interpreter.parse_cache().unwrap();
+    assert_eq!((cache.hits, cache.misses), (1, 2));
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "3\n3\n7\n"
+    );
+}
+
+#[test]
+fn parse_cache_keys_on_parser_options() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source("print 7 / 2;");
+    interpreter.set_output(output.clone());
+    interpreter.enable_parse_cache();
+
+    interpreter.run();
+    interpreter.integers = true;
+    interpreter.run();
+    interpreter.run();
+
+    let cache = interpreter.parse_cache().unwrap();
+    assert_eq!((cache.hits, cache.misses), (1, 2));
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "3.5\n3\n3\n"
+    );
+}
+
+#[test]
+fn eval_more_keeps_globals_between_calls() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source("");
+    interpreter.set_output(output.clone());
+
+    interpreter.eval_more("var count = 1; fun double(n) { return n * 2; }");
+    interpreter.eval_more("print double(count);");
+    interpreter.eval_more("count = 100; print missing;");
+    assert_eq!(interpreter.exit_code, 70);
+    interpreter.eval_more("print (;");
+    assert_eq!(interpreter.exit_code, 65);
+    interpreter.eval_more("print double(count);");
+    assert_eq!(interpreter.exit_code, 0);
+
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "2\n2\n"
+    );
+}
+
+#[test]
+fn binding_policy_decides_which_global_a_function_sees() {
+    let source = "var greeting = \"hello\";
+fun greet() { return greeting; }
+fun countdown(n) { if (n > 0) return countdown(n - 1); return \"done\"; }
+var greeting = \"bye\";
+print greet();
+print countdown(3);";
+
+    let late = run_with(source, |_| {});
+    let early = run_with(source, |interpreter| {
+        interpreter.binding = BindingPolicy::Early
+    });
+
+    assert_eq!(late, "bye\ndone\n");
+    assert_eq!(early, "hello\ndone\n");
+}
+
+#[test]
+fn scan_errors_stop_run_like_check() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source("print 1; @ print 2;\n");
+    interpreter.set_output(output.clone());
+    interpreter.run();
+
+    assert_eq!(interpreter.exit_code, 65);
+    assert!(output.borrow().is_empty());
+
+    let mut checked = Interpreter::from_source("print 1; @ print 2;\n");
+    checked.check();
+    assert_eq!(checked.exit_code, 65);
+}
+
+#[test]
+fn diagnostics_are_one_json_array() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source("print 1 +;\nprint (2;\n");
+    interpreter.set_output(output.clone());
+    interpreter.diagnostics();
+
+    assert_eq!(interpreter.exit_code, 65);
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "[{\"severity\":\"error\",\"line\":1,\"column\":10,\"message\":\"Expect expression.\",\"span\":{\"start\":9,\"end\":10}},\
+         {\"severity\":\"error\",\"line\":2,\"column\":9,\"message\":\"Expect ')' after expression.\",\"span\":{\"start\":19,\"end\":20}}]\n"
+    );
+}
+
+#[test]
+fn time_reports_every_phase() {
+    let mut interpreter = Interpreter::from_source("var a = 1; print a;");
+    interpreter.set_output(Rc::new(RefCell::new(Vec::new())));
+    interpreter.time = true;
+    interpreter.run();
+
+    let report = interpreter.timings().to_string();
+    let phases: Vec<&str> = report
+        .lines()
+        .map(|line| line.split(':').next().unwrap())
+        .collect();
+    assert_eq!(phases, ["[time] scan", "[time] parse", "[time] evaluate"]);
+}
+
+#[test]
+fn unassigned_variable_is_nil_by_default() {
+    let printed = run_with("var x; print x; x = 1; print x;", |_| {});
+
+    assert_eq!(printed, "nil\n1\n");
+}
+
+#[test]
+fn strict_variables_reject_reading_before_assignment() {
+    let printed = run_with(
+        "var x; x = 1; print x; var y = nil; print y;",
+        |interpreter| interpreter.strict_variables = true,
+    );
+    assert_eq!(printed, "1\nnil\n");
+
+    let mut interpreter = Interpreter::from_source("var x; print x;");
+    interpreter.set_output(Rc::new(RefCell::new(Vec::new())));
+    interpreter.strict_variables = true;
+    interpreter.run();
+    assert_eq!(interpreter.exit_code, 70);
+}
+
+#[test]
+fn run_with_env_sees_injected_globals() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source("print greeting + \"!\"; print now() > 0;");
+    interpreter.set_output(output.clone());
+
+    let globals = Environment::globals()
+        .with_value(
+            "greeting",
+            EnvironmentValue::Expr(Expr::String(String::from("hi"))),
+        )
+        .with_value("now", Environment::globals().try_get("clock").unwrap());
+    interpreter.run_with_env(Rc::new(RefCell::new(globals)));
+
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "hi!\ntrue\n"
+    );
+}
+
+#[test]
+fn logical_operators_return_operands_unless_strict() {
+    let source = "print 1 and 2; print nil or \"x\"; print false or nil;";
+
+    assert_eq!(run_with(source, |_| {}), "2\nx\nnil\n");
+    assert_eq!(
+        run_with(source, |interpreter| interpreter.strict_booleans = true),
+        "true\ntrue\nfalse\n"
+    );
+}
+
+#[test]
+fn print_expressions_give_the_printed_value() {
+    let source = "fun compute() { return 6 * 7; }\nvar y = print compute();\nprint y + 1;";
+
+    let printed = run_with(source, |interpreter| interpreter.print_expressions = true);
+    assert_eq!(printed, "42\n43\n");
+
+    let mut interpreter = Interpreter::from_source(source);
+    interpreter.set_output(Rc::new(RefCell::new(Vec::new())));
+    interpreter.run();
+    assert_eq!(interpreter.exit_code, 65);
+}
+
+#[test]
+fn assert_eq_names_both_values() {
+    assert_eq!(
+        run_with(
+            "assert_eq(1 + 1, 2); assert_eq(\"a\", \"a\"); print \"ok\";",
+            |_| {}
+        ),
+        "ok\n"
+    );
+    assert_eq!(
+        runtime_error("assert_eq(1, 2);").to_string(),
+        "Assertion failed: 1 != 2."
+    );
+    assert_eq!(
+        runtime_error("assert_eq([1, \"two\"], nil);").to_string(),
+        "Assertion failed: [1, two] != nil."
+    );
+}
+
+#[test]
+fn assert_eq_compares_like_equality() {
+    assert_eq!(
+        run_with("fun f() {}\nassert_eq(f, f);\nprint \"same\";", |_| {}),
+        "same\n"
+    );
+    assert_eq!(
+        run_with(
+            "print 1 == 1.0;\nassert_eq(1, 1.0);\nprint \"ok\";",
+            |interpreter| { interpreter.integers = true }
+        ),
+        "true\nok\n"
+    );
+    assert_eq!(
+        runtime_error("fun f() {}\nfun g() {}\nassert_eq(f, g);").to_string(),
+        "Assertion failed: <fn f> != <fn g>."
+    );
+}
+
+#[test]
+fn only_functions_are_hoisted() {
+    let mut interpreter = Interpreter::from_source(
+        "print later();\nprint x;\nvar x = 1;\nfun later() { return \"called\"; }",
+    );
+    let output = Rc::new(RefCell::new(Vec::new()));
+    interpreter.set_output(output.clone());
+    interpreter.run();
+
+    assert_eq!(interpreter.exit_code, 70);
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "called\n"
+    );
+}
+
+#[test]
+fn strict_turns_warnings_into_errors() {
+    let source = "fun f() {\n  var unused = 1;\n  return 2;\n}\nprint f();";
+
+    for (strict, exit_code) in [(false, 0), (true, 65)] {
+        let mut interpreter = Interpreter::from_source(source);
+        interpreter.set_output(Rc::new(RefCell::new(Vec::new())));
+        interpreter.strict = strict;
+        interpreter.run();
+        assert_eq!(interpreter.exit_code, exit_code);
+    }
+}
+
+#[test]
+fn scripts_run_in_parallel_threads() {
+    let sources = [
+        "fun fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + fib(n - 2);\n}\nprint fib(10);",
+        "var total = 0;\nfor (var i = 1; i <= 100; i = i + 1) total = total + i;\nprint total;",
+    ];
+
+    let threads = sources.map(|source| std::thread::spawn(move || run_with(source, |_| {})));
+    let outputs = threads.map(|thread| thread.join().unwrap());
+
+    assert_eq!(outputs, ["55\n", "5050\n"]);
+}
+
+#[test]
+fn integers_stay_exact_until_a_float_is_involved() {
+    let source = "print 5 / 2;\nprint -7 / 2;\nprint 5 / 2.0;\nprint 9007199254740993 + 0;\n\
+                  print 0xFF * 1_000;\nprint 1e3;\nprint 1 == 1.0;\nprint [10, 20][1];";
+    assert_eq!(
+        run_with(source, |interpreter| interpreter.integers = true),
+        "2\n-3\n2.5\n9007199254740993\n255000\n1000\ntrue\n20\n"
+    );
+    assert_eq!(
+        run_with("print 5 / 2;", |_| {}),
+        "2.5\n",
+        "floats stay the default"
+    );
+
+    let mut interpreter = Interpreter::from_source("print 9223372036854775807 + 1;");
+    interpreter.set_output(Rc::new(RefCell::new(Vec::new())));
+    interpreter.integers = true;
+    interpreter.run();
+    assert_eq!(interpreter.exit_code, 70);
+}
+
+#[test]
+fn floor_div_and_mod_reject_a_zero_divisor() {
+    for source in ["floor_div(1, 0);", "mod(1.5, 0);", "mod(\"1\", 2);"] {
+        assert!(matches!(runtime_error(source), RuntimeError::Error(_)));
+    }
+    assert_eq!(
+        runtime_error("floor_div(3, 0);").to_string(),
+        "Division by zero."
+    );
+}
+
+#[test]
+fn assigning_an_undeclared_variable_is_an_error() {
+    assert_eq!(
+        runtime_error("var a = 1;\nb = 2;").to_string(),
+        "Undefined variable 'b'.\n[line 2]"
+    );
+    assert_eq!(
+        runtime_error("var a = 1;\nprint b;").to_string(),
+        "Undefined variable 'b'.\n[line 2]"
+    );
+
+    let globals = Rc::new(RefCell::new(Environment::new()));
+    let mut scope = Environment::new();
+    scope.set_enclosing(globals.clone());
+    globals
+        .borrow()
+        .define("a", EnvironmentValue::Expr(Expr::Nil));
+
+    assert!(scope
+        .assign("a", EnvironmentValue::Expr(Expr::Number(1.0)))
+        .is_ok());
+    assert_eq!(
+        globals.borrow().try_get("a"),
+        Some(EnvironmentValue::Expr(Expr::Number(1.0)))
+    );
+    assert!(scope
+        .assign("b", EnvironmentValue::Expr(Expr::Nil))
+        .is_err());
+    assert_eq!(scope.try_get("b"), None);
+}
+
+#[test]
+fn number_formatter_writes_every_printed_number() {
+    let printed = run_with(
+        "print 1234.5; print [0.1, 2]; print {total: 1e6}; print \"1234.5\";",
+        |interpreter| {
+            interpreter.set_number_formatter(|n| {
+                let cents = format!("{:.2}", n);
+                let (whole, fraction) = cents.split_once('.').unwrap();
+                let mut grouped = String::new();
+                for (i, digit) in whole.chars().enumerate() {
+                    if i > 0 && (whole.len() - i) % 3 == 0 {
+                        grouped.push(',');
+                    }
+                    grouped.push(digit);
+                }
+                format!("{grouped}.{fraction}")
+            })
+        },
+    );
+
+    assert_eq!(
+        printed,
+        "1,234.50\n[0.10, 2.00]\n{total: 1,000,000.00}\n1234.5\n"
+    );
+}
+
+#[test]
+fn check_only_reports_scan_and_parse_errors() {
+    let source = "print 1;\nprint undefined;";
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut checked = Interpreter::from_source(source);
+    checked.set_output(output.clone());
+    checked.check();
+    assert_eq!(checked.exit_code, 0);
+    assert!(output.borrow().is_empty());
+
+    let mut ran = Interpreter::from_source(source);
+    ran.set_output(output.clone());
+    ran.run();
+    assert_eq!(ran.exit_code, 70);
+
+    for broken in ["print 1 @;", "print (;"] {
+        let mut interpreter = Interpreter::from_source(broken);
+        interpreter.check();
+        assert_eq!(interpreter.exit_code, 65);
+    }
+}
+
+#[test]
+fn arity_and_name_reject_values_that_are_not_functions() {
+    for source in ["arity(1);", "name(\"clock\");", "name(nil);"] {
+        assert_eq!(
+            runtime_error(source).to_string(),
+            "Argument must be a function."
+        );
+    }
+}
+
+#[test]
+fn sort_comparators_must_take_two_arguments() {
+    for source in [
+        "sort([2, 1], to_bool);",
+        "sort([2, 1], clock);",
+        "fun f(a) { return 0; }\nsort([2, 1], f);",
+    ] {
+        assert_eq!(
+            runtime_error(source).to_string(),
+            "Comparator must take 2 arguments."
+        );
+    }
+}
+
+#[test]
+fn print_result_shows_a_trailing_expression() {
+    let source = "var a = 1;\na + 1;\n2 + 2;";
+    assert_eq!(run_with(source, |_| {}), "");
+    assert_eq!(
+        run_with(source, |interpreter| interpreter.print_result = true),
+        "4\n"
+    );
+
+    // Only a bare expression at the very end is shown, and nil never is
+    for source in ["2 + 2;\nvar b = 3;", "fun f() {}\nf();"] {
+        assert_eq!(
+            run_with(source, |interpreter| interpreter.print_result = true),
+            ""
+        );
+    }
+}
+
+#[test]
+fn array_mutators_check_their_index() {
+    for (source, message) in [
+        ("remove([1, 2], 2);", "Index 2 is out of bounds."),
+        ("remove([], 0);", "Index 0 is out of bounds."),
+        ("insert([1], 2, 0);", "Index 2 is out of bounds."),
+        (
+            "insert([1], -1, 0);",
+            "Index must be a non-negative integer.",
+        ),
+        ("push(\"text\", 1);", "First argument must be an array."),
+    ] {
+        assert_eq!(runtime_error(source).to_string(), message, "{}", source);
+    }
+}
+
+#[test]
+fn read_all_returns_the_input_verbatim() {
+    let input = "first line\n  indented\r\n\nlast without newline";
+    let printed = run_with(
+        "var text = read_all();\nprint text;\nprint text == \"first line\\n  indented\\r\\n\\nlast without newline\";\nprint read_all() == \"\";",
+        |interpreter| interpreter.set_input(Rc::new(RefCell::new(io::Cursor::new(input)))),
+    );
+
+    assert_eq!(printed, format!("{input}\ntrue\ntrue\n"));
+}
+
+#[test]
+fn run_capture_returns_everything_printed() {
+    let printed = Interpreter::run_capture(
+        "print \"start\";\nfor (var i = 1; i <= 3; i = i + 1) print i * i;\nprint double(21);\nfun double(n) { return n * 2; }",
+    );
+    assert_eq!(printed, Ok(String::from("start\n1\n4\n9\n42\n")));
+    assert_eq!(
+        Interpreter::run_capture("print 1; exit(0); print 2;"),
+        Ok(String::from("1\n"))
+    );
+
+    assert!(matches!(
+        Interpreter::run_capture("print @;"),
+        Err(LoxError::Scan(_))
+    ));
+    assert!(matches!(
+        Interpreter::run_capture("print (;"),
+        Err(LoxError::Parse(_))
+    ));
+    assert_eq!(
+        Interpreter::run_capture("print 1; print missing;")
+            .unwrap_err()
+            .to_string(),
+        "Undefined variable 'missing'.\n[line 1]"
+    );
+}
+
+#[test]
+fn no_value_prints_as_null() {
+    let printed = run_with(
+        "var unset;\nfun nothing() {}\nfun early() { return; }\nvar items = [nil, unset];\nprint unset;\nprint nothing();\nprint early();\nprint items;\nprint pop([]);\nprint {a: nil}.a;\nprint nil ?? nil;\nprint nil?.field;\nprint num(\"x\");",
+        |_| {},
+    );
+
+    assert!(!printed.contains("null"), "{}", printed);
+    assert_eq!(printed.matches("nil").count(), 10);
+}
+
+#[test]
+fn callbacks_must_take_the_right_number_of_arguments() {
+    let functions = "fun one(a) { return a; }\nfun two(a, b) { return a; }\n";
+    for (call, message) in [
+        ("map([1], two);", "Callback must take 1 argument."),
+        ("filter([1], two);", "Callback must take 1 argument."),
+        ("reduce([1], one, 0);", "Callback must take 2 arguments."),
+        ("map([1], 1);", "Callback must be a function."),
+        ("map([1], clock);", "Callback must take 1 argument."),
+        (
+            "reduce([1], to_bool, 0);",
+            "Callback must take 2 arguments.",
+        ),
+        ("apply(clock, [1]);", "Expected 0 arguments but got 1."),
+        (
+            "apply(sort, [[], nil, 1]);",
+            "Expected 1 to 2 arguments but got 3.",
+        ),
+        ("filter(nil, one);", "First argument must be an array."),
+    ] {
+        assert_eq!(
+            runtime_error(&format!("{functions}{call}")).to_string(),
+            message,
+            "{}",
+            call
+        );
+    }
+}
+
+#[test]
+fn bad_calls_report_the_line_of_the_call() {
+    let arity = Interpreter::run_capture("fun f(a) { return a; }\n\nprint f(1, 2);").unwrap_err();
+    assert_eq!(
+        arity.to_string(),
+        "Expected 1 arguments but got 2.\n[line 3]"
+    );
+    let native = Interpreter::run_capture("var t = 1;\nprint clock(t);").unwrap_err();
+    assert_eq!(
+        native.to_string(),
+        "Expected 0 arguments but got 1.\n[line 2]"
+    );
+
+    let not_callable = Interpreter::run_capture("var x = 3;\nprint 1;\nx();").unwrap_err();
+    assert_eq!(
+        not_callable.to_string(),
+        "Can only call functions and classes.\n[line 3]"
+    );
+    assert!(matches!(
+        not_callable,
+        LoxError::Runtime(RuntimeError::At { line: 3, .. })
+    ));
+}
+
+#[test]
+fn sleep_gives_nil_and_returns_at_once_in_a_sandbox() {
+    assert_eq!(run_with("print sleep(0.001);", |_| {}), "nil\n");
+
+    let started = Instant::now();
+    let printed = run_with("print sleep(60);", |interpreter| interpreter.sandbox = true);
+    assert_eq!(printed, "nil\n");
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    assert_eq!(
+        runtime_error("sleep(-1);").to_string(),
+        "Argument must be a non-negative number."
+    );
+    assert_eq!(
+        runtime_error("sleep(\"1\");").to_string(),
+        "Argument must be a non-negative number."
+    );
+    assert_eq!(
+        runtime_error("sleep(0 / 0);").to_string(),
+        "Argument must be a non-negative number."
+    );
+    assert_eq!(
+        runtime_error("sleep(1e30);").to_string(),
+        "Can't sleep that long."
+    );
+}
+
+#[test]
+fn conversions_keep_arithmetic_strict() {
+    assert_eq!(
+        runtime_error("print true + 1;").to_string(),
+        "Operands must be two numbers or two strings.\n[line 1]"
+    );
+    assert_eq!(
+        runtime_error("to_number(\"12abc\");").to_string(),
+        "Argument must be a boolean or a number."
+    );
+}
+
+#[test]
+fn evaluate_prints_runtime_number_spellings() {
+    for (source, expected) in [
+        ("1.50", "1.5\n"),
+        ("100.00", "100\n"),
+        ("1.50 + 100", "101.5\n"),
+    ] {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::from_source(source);
+        interpreter.set_output(output.clone());
+        interpreter.evaluate();
+
+        assert_eq!(
+            String::from_utf8(output.borrow().clone()).unwrap(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn exit_stops_the_program_and_sets_the_exit_code() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source("print 1;\nexit(3);\nprint 2;");
+    interpreter.set_output(output.clone());
+    interpreter.run();
+
+    assert_eq!(interpreter.exit_code, 3);
+    assert_eq!(String::from_utf8(output.borrow().clone()).unwrap(), "1\n");
+}
+
+#[test]
+fn format_number_rejects_too_many_decimals() {
+    for source in [
+        "format_number(1, 70000);",
+        "format_number(1, 1e20);",
+        "format_number(1, -1);",
+        "format_number(1, 0.5);",
+    ] {
+        assert_eq!(
+            runtime_error(source).to_string(),
+            "Decimals must be an integer from 0 to 100."
+        );
+    }
+    assert_eq!(
+        run_with(
+            "print format_number(1, 100) == format_number(1, 100);",
+            |_| {}
+        ),
+        "true\n"
+    );
+}
@@ -0,0 +1,344 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use codecrafters_interpreter::environment::Environment;
+use codecrafters_interpreter::evaluator::Evaluator;
+use codecrafters_interpreter::formatters::ast_to_string;
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::scanner::Scanner;
+use codecrafters_interpreter::{Expr, Token, TokenType};
+
+fn parse(source: &str) -> Parser {
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens(source, &mut 0);
+    let mut parser = Parser::new(scanner.tokens);
+    parser.parse();
+    parser
+}
+
+#[test]
+fn assignment_as_condition_warns() {
+    let parser = parse("var x;\nif (x = 5) {}\nwhile (x = nil) {}");
+
+    assert_eq!(
+        parser
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "[line 2] Warning: Assignment used as a condition, did you mean '=='?",
+            "[line 3] Warning: Assignment used as a condition, did you mean '=='?",
+        ]
+    );
+}
+
+#[test]
+fn parenthesized_assignment_as_condition_does_not_warn() {
+    let parser = parse("var x;\nif ((x = 5)) {}\nif (x == 5) {}");
+
+    assert!(parser.warnings.is_empty());
+}
+
+#[test]
+fn statements_after_a_parse_error_are_kept() {
+    let parser = parse("var = 1;\nprint \"first\";\nprint \"second\";");
+
+    assert_eq!(parser.errors.len(), 1);
+    assert_eq!(
+        parser.errors[0].to_string(),
+        "[line 1] Error at '=': Expect variable name."
+    );
+
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let evaluator = Evaluator::with_output(output.clone());
+    let environment = Rc::new(RefCell::new(Environment::new()));
+    for statement in parser.statements.iter() {
+        evaluator.evaluate(statement, &environment, None).unwrap();
+    }
+
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "first\nsecond\n"
+    );
+}
+
+#[test]
+fn every_broken_statement_is_reported() {
+    let parser = parse("print 1 +;\nvar ok = 1;\nprint (2;\nprint ok;");
+
+    assert_eq!(
+        parser
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>(),
+        vec![
+            "[line 1] Error at ';': Expect expression.",
+            "[line 3] Error at ';': Expect ')' after expression.",
+        ]
+    );
+    assert_eq!(parser.statements.len(), 2);
+}
+
+#[test]
+fn parse_errors_name_the_token_found() {
+    let messages = |source: &str| {
+        parse(source)
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        messages("var a = 1;\n\n\nprint (a + 2;"),
+        vec!["[line 4] Error at ';': Expect ')' after expression."]
+    );
+    assert_eq!(
+        messages("print (1"),
+        vec!["[line 1] Error at end: Expect ')' after expression."]
+    );
+    assert_eq!(
+        messages("1 + 2 = 3;"),
+        vec!["[line 1] Error at '=': Invalid assignment target."]
+    );
+}
+
+#[test]
+fn ast_quotes_strings() {
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens(r#""hi\tthere""#, &mut 0);
+    let expression = Parser::new(scanner.tokens).expression().unwrap();
+
+    assert_eq!(expression.to_string(), r#""hi\tthere""#);
+}
+
+#[test]
+fn comparing_nil_is_a_runtime_error() {
+    let parser = parse("print nil < 1;");
+    let evaluator = Evaluator::with_output(Rc::new(RefCell::new(Vec::new())));
+    let environment = Rc::new(RefCell::new(Environment::new()));
+
+    assert_eq!(
+        evaluator
+            .evaluate(&parser.statements[0], &environment, None)
+            .unwrap_err()
+            .to_string(),
+        "Operands must be numbers.\n[line 1]"
+    );
+}
+
+#[test]
+fn postfix_operators_nest_left_to_right() {
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens("obj.items[0](1).name", &mut 0);
+    let expression = Parser::new(scanner.tokens).expression().unwrap();
+
+    let Expr::Get {
+        object,
+        name,
+        optional: false,
+    } = expression
+    else {
+        panic!("expected a property access");
+    };
+    assert_eq!(name.lexeme, "name");
+    let Expr::Call(callee, _, arguments) = *object else {
+        panic!("expected a call");
+    };
+    assert_eq!(arguments.len(), 1);
+    let Expr::Index { object, index, .. } = *callee else {
+        panic!("expected an index");
+    };
+    assert_eq!(index.to_string(), "0.0");
+    assert!(matches!(*object, Expr::Get { ref name, .. } if name.lexeme == "items"));
+}
+
+#[test]
+fn grouping_holds_one_expression() {
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens("((1))", &mut 0);
+    let expression = Parser::new(scanner.tokens).expression().unwrap();
+    assert!(
+        matches!(expression, Expr::Grouping(ref inner) if matches!(**inner, Expr::Grouping(_)))
+    );
+    assert_eq!(expression.to_string(), "((1.0))");
+
+    let parser = parse("print (1, 2);");
+    assert_eq!(
+        parser.errors[0].to_string(),
+        "[line 1] Error at ',': Expect ')' after expression."
+    );
+}
+
+#[test]
+fn deeply_nested_expression_prints_with_ellipsis() {
+    let mut expression = Expr::Nil;
+    for _ in 0..100_000 {
+        expression = Expr::Grouping(Box::new(expression));
+    }
+
+    let printed = expression.to_string();
+    assert!(printed.starts_with("(((("));
+    assert!(printed.contains("..."));
+    assert!(!printed.contains("nil"));
+    assert_eq!(ast_to_string(&expression).matches("(group ").count(), 200);
+
+    // Dropping the boxes one at a time keeps the drop from recursing as deep as the tree
+    while let Expr::Grouping(inner) = expression {
+        expression = *inner;
+    }
+}
+
+#[test]
+fn keywords_cannot_be_declared() {
+    let parser = parse("var if = 1;\nfun while() {}\nfun f(a, return) {}");
+
+    assert_eq!(
+        parser
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>(),
+        vec![
+            "[line 1] Error at 'if': Expect variable name; 'if' is a reserved keyword.",
+            "[line 2] Error at 'while': Expect function name; 'while' is a reserved keyword.",
+            "[line 3] Error at 'return': Expect parameter name; 'return' is a reserved keyword.",
+        ]
+    );
+}
+
+#[test]
+fn stray_semicolons_are_empty_statements() {
+    let parser = parse(";;;\nif (true) ; else print 1;\nwhile (false);");
+    assert!(parser.errors.is_empty());
+
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let evaluator = Evaluator::with_output(output.clone());
+    let environment = Rc::new(RefCell::new(Environment::new()));
+    for statement in parser.statements.iter() {
+        evaluator.evaluate(statement, &environment, None).unwrap();
+    }
+    assert!(output.borrow().is_empty());
+}
+
+#[test]
+fn assignment_targets() {
+    let parser = parse("a = 1;\nm.field = 2;\nlist[0] = 3;\n1 + 2 = 3;");
+
+    assert!(matches!(parser.statements[0], Expr::Assign { ref name, .. } if name.lexeme == "a"));
+    assert!(matches!(parser.statements[1], Expr::Set { ref name, .. } if name.lexeme == "field"));
+    assert!(matches!(parser.statements[2], Expr::IndexSet { .. }));
+    assert_eq!(
+        parser.errors[0].to_string(),
+        "[line 4] Error at '=': Invalid assignment target."
+    );
+}
+
+#[test]
+fn unused_locals_and_unreachable_code_warn() {
+    let parser = parse(
+        "var global = 1;\nfun f(a) {\n  var used = a;\n  var unused = 2;\n  var _ignored = 3;\n  {\n    var used = 4;\n    print used;\n  }\n  return used;\n  print \"never\";\n}",
+    );
+
+    assert_eq!(
+        parser
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        vec![
+            "[line 11] Warning: Unreachable code after 'return'.",
+            "[line 4] Warning: Local variable 'unused' is never used.",
+        ]
+    );
+}
+
+#[test]
+fn break_only_inside_a_loop() {
+    let parser = parse("while (true) break;\ndo { break; } while (true);");
+    assert!(parser.errors.is_empty());
+    assert!(matches!(parser.statements[1], Expr::DoWhile(..)));
+
+    // A function body is outside the loop it is declared in
+    for source in ["break;", "for (;;) { fun f() { break; } }"] {
+        assert_eq!(
+            parse(source).errors[0].message,
+            "Can't use 'break' outside of a loop."
+        );
+    }
+}
+
+#[test]
+fn named_arguments_are_rejected() {
+    let parser = parse("print f(1, b ? 2 : 3, {c: 4});\nf(a: 1);");
+
+    assert_eq!(parser.errors.len(), 1);
+    assert_eq!(
+        parser.errors[0].to_string(),
+        "[line 2] Error at ':': Named arguments are not supported."
+    );
+}
+
+#[test]
+fn declarations_and_references_print_differently() {
+    let parser = parse("var x = 1;\nvar a, b = [2, 3];\nx = a;\nprint x;");
+    assert!(parser.errors.is_empty());
+
+    assert!(matches!(parser.statements[0], Expr::VarDecl { .. }));
+    assert_eq!(parser.statements[0].to_string(), "var x = 1.0");
+    assert_eq!(parser.statements[1].to_string(), "var a, b = [2.0, 3.0]");
+    assert_eq!(parser.statements[2].to_string(), "x = a");
+
+    let Expr::Print(reference) = &parser.statements[3] else {
+        panic!("expected a print statement");
+    };
+    assert!(matches!(**reference, Expr::VarRef(_)));
+    assert_eq!(reference.to_string(), "x");
+}
+
+#[test]
+fn loops_that_can_never_stop_warn() {
+    let warnings = |source: &str| {
+        parse(source)
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        warnings("while (true) {}\nfor (;;) { while (true) { break; } }"),
+        vec![
+            "[line 1] Warning: Loop never ends, it has no 'break', 'return' or 'exit'.",
+            "[line 2] Warning: Loop never ends, it has no 'break', 'return' or 'exit'.",
+        ]
+    );
+    for source in [
+        "while (true) { break; }",
+        "fun f() { for (;;) return 1; }",
+        "do { exit(0); } while (true);",
+        "while (false) {}",
+    ] {
+        assert!(warnings(source).is_empty(), "{}", source);
+    }
+}
+
+#[test]
+fn literal_token_without_a_value_is_an_error() {
+    let tokens = vec![
+        Token::new(TokenType::PRINT, String::from("print"), None, 1),
+        Token::new(TokenType::NUMBER, String::from("1"), None, 1),
+        Token::new(TokenType::SEMICOLON, String::from(";"), None, 1),
+        Token::new(TokenType::EOF, String::new(), None, 1),
+    ];
+    let mut parser = Parser::new(tokens);
+    parser.parse();
+
+    assert!(parser.statements.is_empty());
+    assert_eq!(
+        parser.errors[0].to_string(),
+        "[line 1] Error at '1': Literal token has no value."
+    );
+}
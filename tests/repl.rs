@@ -0,0 +1,88 @@
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use codecrafters_interpreter::repl::Repl;
+
+#[test]
+fn loaded_functions_are_callable_from_the_first_input() {
+    let path = std::env::temp_dir().join("repl_load_library.lox");
+    fs::write(&path, "fun square(n) { return n * n; }\nvar base = 3;\n").unwrap();
+
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut repl = Repl::new();
+    repl.set_output(output.clone());
+    repl.load(path.to_str().unwrap()).unwrap();
+    repl.read("print square(base);\n".as_bytes());
+
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "> 9\n> "
+    );
+}
+
+#[test]
+fn load_stops_at_the_first_error() {
+    let path = std::env::temp_dir().join("repl_load_broken.lox");
+    fs::write(&path, "var ok = 1;\nprint (;\n").unwrap();
+
+    let mut repl = Repl::new();
+    let error = repl.load(path.to_str().unwrap()).unwrap_err();
+
+    assert_eq!(error, "[line 2] Error at ';': Expect expression.");
+}
+
+#[test]
+fn bare_expressions_print_their_value_after_the_prefix() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut repl = Repl::new();
+    repl.set_output(output.clone());
+    repl.set_prompt("lox> ");
+    repl.set_result_prefix("=> ");
+    repl.read("var a = 2;\na + 2;\nprint a;\nfun f() {}\nf();\n(a +\n1);\n".as_bytes());
+
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "lox> lox> => 4\nlox> 2\nlox> lox> lox> ... => 3\nlox> "
+    );
+}
+
+#[test]
+fn exit_stops_the_session_with_its_code() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut repl = Repl::new();
+    repl.set_output(output.clone());
+    repl.read("print 1;\nexit(3); print 2;\nprint 3;\n".as_bytes());
+
+    assert_eq!(repl.exit_code(), Some(3));
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "> 1\n> "
+    );
+}
+
+#[test]
+fn escaped_quotes_and_open_brackets_are_handled() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut repl = Repl::new();
+    repl.set_output(output.clone());
+    repl.read("print \"\\\"\";\nprint [1,\n2];\n".as_bytes());
+
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "> \"\n> ... [1, 2]\n> "
+    );
+}
+
+#[test]
+fn input_with_a_scan_error_does_not_run() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut repl = Repl::new();
+    repl.set_output(output.clone());
+    repl.read("print 1; @ print 2;\nprint 3;\n".as_bytes());
+
+    assert_eq!(
+        String::from_utf8(output.borrow().clone()).unwrap(),
+        "> > 3\n> "
+    );
+}
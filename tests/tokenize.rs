@@ -0,0 +1,250 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use codecrafters_interpreter::error::ScanErrorKind;
+use codecrafters_interpreter::interpreter::Interpreter;
+use codecrafters_interpreter::scanner::{to_source, Scanner};
+use codecrafters_interpreter::{Literal, TokenType};
+
+fn tokenize(source: &str) -> String {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut interpreter = Interpreter::from_source(source);
+    interpreter.set_output(output.clone());
+    interpreter.tokenize();
+
+    let printed = output.borrow().clone();
+    String::from_utf8(printed).unwrap()
+}
+
+#[test]
+fn number_lexemes_keep_source_spelling() {
+    assert_eq!(
+        tokenize("0xFF 1_000 1e3 2.5E-1 0b101 0o17"),
+        "NUMBER 0xFF 255.0\n\
+         NUMBER 1_000 1000.0\n\
+         NUMBER 1e3 1000.0\n\
+         NUMBER 2.5E-1 0.25\n\
+         NUMBER 0b101 5.0\n\
+         NUMBER 0o17 15.0\n\
+         EOF  null\n"
+    );
+}
+
+#[test]
+fn exponent_without_digits_is_an_identifier() {
+    assert_eq!(
+        tokenize("1e"),
+        "NUMBER 1 1.0\nIDENTIFIER e null\nEOF  null\n"
+    );
+}
+
+#[test]
+fn only_literal_tokens_carry_a_literal() {
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens("nil + \"s\" 2", &mut 0);
+
+    let literals = scanner
+        .tokens
+        .iter()
+        .map(|token| token.literal.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        literals,
+        vec![
+            None,
+            None,
+            Some(Literal::String(String::from("s"))),
+            Some(Literal::Number((2.0, 0))),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn tokens_without_literal_print_null() {
+    assert_eq!(
+        tokenize("nil != x"),
+        "NIL nil null\nBANG_EQUAL != null\nIDENTIFIER x null\nEOF  null\n"
+    );
+}
+
+#[test]
+fn malformed_unicode_escapes_are_scan_errors() {
+    for source in ["\"\\u{zz}\"", "\"\\u{110000}\"", "\"\\u41\"", "\"\\u{41\""] {
+        let mut scanner = Scanner::new();
+        let mut error_code = 0;
+        scanner.scan_tokens(source, &mut error_code);
+
+        assert_eq!(error_code, 65, "{}", source);
+        assert_eq!(scanner.tokens.len(), 1, "{}", source);
+    }
+}
+
+#[test]
+fn iterator_scans_lazily() {
+    let mut scanner = Scanner::new();
+    let lexemes = scanner
+        .iter("var a = 1; @ \"unterminated")
+        .take(4)
+        .map(|token| token.unwrap().lexeme)
+        .collect::<Vec<_>>();
+
+    assert_eq!(lexemes, vec!["var", "a", "=", "1"]);
+}
+
+#[test]
+fn iterator_yields_errors_and_ends_with_eof() {
+    let mut scanner = Scanner::new();
+    let results = scanner.iter("1 @ 2").collect::<Vec<_>>();
+
+    assert_eq!(results.len(), 4);
+    assert_eq!(
+        results[1].as_ref().unwrap_err().to_string(),
+        "[line 1] Error: Unexpected character: @"
+    );
+    assert_eq!(results[3].as_ref().unwrap().token_type, TokenType::EOF);
+}
+
+#[test]
+fn string_literal_is_the_raw_value() {
+    assert_eq!(
+        tokenize(r#""hi\tthere""#),
+        "STRING \"hi\\tthere\" hi\tthere\nEOF  null\n"
+    );
+}
+
+#[test]
+fn integer_literals_print_with_a_fraction() {
+    assert_eq!(
+        tokenize("42 42.0"),
+        "NUMBER 42 42.0\nNUMBER 42.0 42.0\nEOF  null\n"
+    );
+}
+
+#[test]
+fn to_source_scans_back_to_the_same_tokens() {
+    let source = "// totals\nfun total(items) {\n  var sum = 0x10; /* start */\n  for (var i = 0; i < 3; i = i + 1) sum = sum + items[i];\n  return sum >= 1_000 and r\"a\\b\" != \"two\nlines\";\n}\nprint total([1, 2.50, 3]);\n";
+    let scan = |source: &str| {
+        let mut scanner = Scanner::new();
+        scanner.scan_tokens(source, &mut 0);
+        scanner
+            .tokens
+            .into_iter()
+            .map(|token| (token.token_type, token.lexeme, token.literal, token.line))
+            .collect::<Vec<_>>()
+    };
+
+    let mut scanner = Scanner::new();
+    scanner.scan_tokens(source, &mut 0);
+    let written = to_source(&scanner.tokens);
+
+    assert_eq!(scan(&written), scan(source));
+}
+
+#[test]
+fn every_line_ending_counts_as_one_line() {
+    for newline in ["\n", "\r\n", "\r"] {
+        let source = [
+            "var a = 1;",
+            "// note",
+            "/* block",
+            "*/ \"two",
+            "lines\";",
+            "  @",
+        ]
+        .join(newline);
+        let error = Scanner::new().iter(&source).find_map(Result::err).unwrap();
+
+        assert_eq!(
+            (error.to_string(), error.column),
+            (String::from("[line 6] Error: Unexpected character: @"), 3),
+            "line ending {:?}",
+            newline
+        );
+    }
+}
+
+#[test]
+fn second_decimal_point_is_a_malformed_number() {
+    let results = Scanner::new().iter("1.2.3 + 4").collect::<Vec<_>>();
+
+    let error = results[0].as_ref().unwrap_err();
+    assert_eq!(error.to_string(), "[line 1] Error: Malformed number.");
+    assert_eq!((error.span.start, error.span.end), (0, 5));
+    assert_eq!(results[1].as_ref().unwrap().token_type, TokenType::PLUS);
+}
+
+#[test]
+fn dots_without_a_fraction_between_them_are_not_a_malformed_number() {
+    assert_eq!(
+        tokenize("1..2"),
+        "NUMBER 1 1.0\nDOT . null\nDOT . null\nNUMBER 2 2.0\nEOF  null\n"
+    );
+}
+
+#[test]
+fn scanning_continues_after_an_unexpected_character() {
+    let mut scanner = Scanner::new();
+    let (tokens, errors): (Vec<_>, Vec<_>) = scanner
+        .iter("var a = 1 @ + b;\nprint a;")
+        .partition(Result::is_ok);
+
+    let tokens = tokens
+        .into_iter()
+        .map(|token| {
+            let token = token.unwrap();
+            (token.lexeme, token.line, token.column)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens[4..7],
+        [
+            (String::from("+"), 1, 13),
+            (String::from("b"), 1, 15),
+            (String::from(";"), 1, 16),
+        ]
+    );
+    assert_eq!(tokens.len(), 11);
+
+    let errors = errors
+        .into_iter()
+        .map(|error| error.unwrap_err().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(errors, ["[line 1] Error: Unexpected character: @"]);
+}
+
+#[test]
+fn very_long_identifiers_and_strings_are_single_tokens() {
+    let name = "a".repeat(200_000);
+    let text = "b".repeat(200_000);
+    let source = format!("{name} \"{text}\" while");
+
+    let tokens = Scanner::new()
+        .iter(&source)
+        .map(Result::unwrap)
+        .collect::<Vec<_>>();
+    assert_eq!(tokens.len(), 4);
+    assert_eq!(tokens[0].token_type, TokenType::IDENTIFIER);
+    assert_eq!(tokens[0].lexeme, name);
+    assert_eq!(tokens[1].literal, Some(Literal::String(text)));
+    assert_eq!(tokens[2].token_type, TokenType::WHILE);
+}
+
+#[test]
+fn scan_errors_carry_their_kind() {
+    let cases = [
+        ("@", ScanErrorKind::UnexpectedChar),
+        ("\"open", ScanErrorKind::UnterminatedString),
+        ("/* open", ScanErrorKind::UnterminatedComment),
+        ("\"\\q\"", ScanErrorKind::InvalidEscape),
+        ("\"\\u{zz}\"", ScanErrorKind::InvalidEscape),
+        ("1.2.3", ScanErrorKind::MalformedNumber),
+    ];
+    for (source, kind) in cases {
+        let error = Scanner::new()
+            .iter(source)
+            .find_map(Result::err)
+            .expect(source);
+        assert_eq!(error.kind, kind, "{}", source);
+    }
+}